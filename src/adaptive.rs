@@ -0,0 +1,104 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+/// Switching between the slow and the fast sampling interval.
+///
+/// The fast interval is used while the rate of change of the readings is
+/// above `fast_above`, until it falls below `slow_below`. The gap between
+/// both is the hysteresis, which avoids flapping on noisy readings.
+#[derive(Debug, Clone, Copy)]
+pub struct Adaptive {
+    pub slow_interval: Duration,
+    pub fast_interval: Duration,
+    pub fast_above: f64,
+    pub slow_below: f64,
+    /// Number of recent readings for the rate of change.
+    pub window: usize,
+}
+
+impl Adaptive {
+    pub fn describe(&self) -> String {
+        format!(
+            "{} seconds above {} per second, back below {} per second over {} readings",
+            self.fast_interval.as_secs_f64(),
+            self.fast_above,
+            self.slow_below,
+            self.window
+        )
+    }
+}
+
+/// Change of the sampling interval.
+pub struct Change {
+    pub interval: Duration,
+    /// Rate of change per second, which caused the change.
+    pub rate: f64,
+}
+
+/// Tracks the rate of change of the recent readings.
+pub struct Detector {
+    adaptive: Adaptive,
+    recent: VecDeque<(f64, f64)>,
+    fast: bool,
+}
+
+impl Detector {
+    pub fn new(adaptive: Adaptive) -> Detector {
+        Detector {
+            adaptive,
+            recent: VecDeque::with_capacity(adaptive.window),
+            fast: false,
+        }
+    }
+
+    /// Adds a reading taken at `moment` seconds, returns the new sampling
+    /// interval when switching.
+    pub fn update(&mut self, moment: f64, reading: f64) -> Option<Change> {
+        if self.recent.len() == self.adaptive.window {
+            self.recent.pop_front();
+        }
+        self.recent.push_back((moment, reading));
+
+        let rate = self.rate()?.abs();
+
+        if !self.fast && rate > self.adaptive.fast_above {
+            self.fast = true;
+            Some(Change {
+                interval: self.adaptive.fast_interval,
+                rate,
+            })
+        } else if self.fast && rate < self.adaptive.slow_below {
+            self.fast = false;
+            Some(Change {
+                interval: self.adaptive.slow_interval,
+                rate,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Slope of the least squares line through the recent readings.
+    fn rate(&self) -> Option<f64> {
+        if self.recent.len() < 2 {
+            return None;
+        }
+
+        let count = self.recent.len() as f64;
+        let mean_moment = self.recent.iter().map(|(moment, _)| moment).sum::<f64>() / count;
+        let mean_reading = self.recent.iter().map(|(_, reading)| reading).sum::<f64>() / count;
+
+        let (covariance, variance) =
+            self.recent
+                .iter()
+                .fold((0.0, 0.0), |(covariance, variance), (moment, reading)| {
+                    let dt = moment - mean_moment;
+                    (
+                        covariance + dt * (reading - mean_reading),
+                        variance + dt * dt,
+                    )
+                });
+
+        (variance > 0.0).then(|| covariance / variance)
+    }
+}
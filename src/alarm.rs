@@ -0,0 +1,225 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use chrono::prelude::*;
+use serde_json::{json, Value};
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Limits on the reading and what to do when they are violated.
+///
+/// An alarm is raised when the reading exceeds a limit and cleared when it
+/// returns within the limit by at least the hysteresis.
+#[derive(Debug, Clone)]
+pub struct Alarms {
+    pub upper: Option<f64>,
+    pub lower: Option<f64>,
+    pub hysteresis: f64,
+    /// Shell command run with the event as JSON in `DMM_ALARM` and stdin.
+    pub exec: Option<String>,
+    /// URL the event is posted to as JSON.
+    pub url: Option<String>,
+    /// Beep the instrument, when an alarm is raised.
+    pub beep: bool,
+}
+
+impl Alarms {
+    pub fn describe(&self) -> String {
+        let mut limits = Vec::new();
+
+        if let Some(lower) = self.lower {
+            limits.push(format!("below {lower}"));
+        }
+
+        if let Some(upper) = self.upper {
+            limits.push(format!("above {upper}"));
+        }
+
+        format!("{}, hysteresis {}", limits.join(" or "), self.hysteresis)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Limit {
+    Upper,
+    Lower,
+}
+
+pub struct Event {
+    pub raised: bool,
+    pub limit: Limit,
+    pub threshold: f64,
+    pub reading: f64,
+}
+
+impl Event {
+    pub fn describe(&self, sequence: u32) -> String {
+        match (self.raised, self.limit) {
+            (true, Limit::Upper) => format!(
+                "{sequence}: Alarm, reading {} above upper limit {}",
+                self.reading, self.threshold
+            ),
+            (true, Limit::Lower) => format!(
+                "{sequence}: Alarm, reading {} below lower limit {}",
+                self.reading, self.threshold
+            ),
+            (false, Limit::Upper) => format!(
+                "{sequence}: Alarm cleared, reading {} back below upper limit {}",
+                self.reading, self.threshold
+            ),
+            (false, Limit::Lower) => format!(
+                "{sequence}: Alarm cleared, reading {} back above lower limit {}",
+                self.reading, self.threshold
+            ),
+        }
+    }
+
+    pub fn to_json(&self, sequence: u32, datetime: DateTime<Local>) -> Value {
+        json!({
+            "type": "alarm",
+            "event": if self.raised { "raised" } else { "cleared" },
+            "limit": match self.limit {
+                Limit::Upper => "upper",
+                Limit::Lower => "lower",
+            },
+            "threshold": self.threshold,
+            "reading": self.reading,
+            "sequence": sequence,
+            "datetime": datetime.to_rfc3339_opts(SecondsFormat::Millis, false),
+        })
+    }
+}
+
+/// Watches the readings for limit violations.
+pub struct Monitor {
+    alarms: Alarms,
+    upper: bool,
+    lower: bool,
+    notifications: Vec<JoinHandle<()>>,
+}
+
+impl Monitor {
+    pub fn new(alarms: Alarms) -> Monitor {
+        Monitor {
+            alarms,
+            upper: false,
+            lower: false,
+            notifications: Vec::new(),
+        }
+    }
+
+    pub fn alarms(&self) -> &Alarms {
+        &self.alarms
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.upper || self.lower
+    }
+
+    /// Returns the alarms raised or cleared by the reading.
+    pub fn check(&mut self, reading: f64) -> Vec<Event> {
+        let hysteresis = self.alarms.hysteresis;
+        let mut events = Vec::new();
+
+        if let Some(upper) = self.alarms.upper {
+            let raised = if self.upper {
+                reading >= upper - hysteresis
+            } else {
+                reading > upper
+            };
+
+            if raised != self.upper {
+                self.upper = raised;
+                events.push(Event {
+                    raised,
+                    limit: Limit::Upper,
+                    threshold: upper,
+                    reading,
+                });
+            }
+        }
+
+        if let Some(lower) = self.alarms.lower {
+            let raised = if self.lower {
+                reading <= lower + hysteresis
+            } else {
+                reading < lower
+            };
+
+            if raised != self.lower {
+                self.lower = raised;
+                events.push(Event {
+                    raised,
+                    limit: Limit::Lower,
+                    threshold: lower,
+                    reading,
+                });
+            }
+        }
+
+        events
+    }
+
+    /// Runs the command and posts to the URL in the background, failures are
+    /// reported on stderr.
+    pub fn notify(&mut self, event: &Value) {
+        self.notifications.retain(|handle| !handle.is_finished());
+
+        let event = event.to_string();
+
+        if let Some(command) = self.alarms.exec.clone() {
+            let event = event.clone();
+            self.notifications.push(thread::spawn(move || {
+                if let Err(err) = run(&command, &event) {
+                    eprintln!("Alarm command `{command}` failed: {err}");
+                }
+            }));
+        }
+
+        if let Some(url) = self.alarms.url.clone() {
+            self.notifications.push(thread::spawn(move || {
+                let result = ureq::post(&url)
+                    .timeout(TIMEOUT)
+                    .set("Content-Type", "application/json")
+                    .send_string(&event);
+
+                if let Err(err) = result {
+                    eprintln!("Posting alarm to '{url}' failed: {err}");
+                }
+            }));
+        }
+    }
+
+    /// Waits for the running notifications, so they are not cut off when
+    /// logging finished.
+    pub fn wait(&mut self) {
+        for handle in self.notifications.drain(..) {
+            let _ = handle.join();
+        }
+    }
+}
+
+fn run(command: &str, event: &str) -> std::io::Result<()> {
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .env("DMM_ALARM", event)
+        .stdin(Stdio::piped())
+        .stdout(std::io::stderr())
+        .spawn()?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        // The command may not read stdin at all.
+        let _ = writeln!(stdin, "{event}");
+    }
+
+    let status = child.wait()?;
+
+    if !status.success() {
+        return Err(std::io::Error::other(format!("exited with {status}")));
+    }
+
+    Ok(())
+}
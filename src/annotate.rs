@@ -0,0 +1,113 @@
+use std::io::{stdin, IsTerminal, Read};
+use std::thread;
+
+use indicatif::{ProgressBar, ProgressDrawTarget};
+
+use crate::control::{Command, Handle};
+
+/// Reads annotations from stdin while logging.
+///
+/// On a terminal, `m` inserts a marker and `a` or `Enter` prompts for an
+/// annotation text. Meanwhile the terminal does not echo the keys, so the
+/// progress bar is not disturbed. Otherwise every line read from stdin is an
+/// annotation, an empty line inserts a marker without text.
+pub struct Annotations {
+    saved: Option<libc::termios>,
+}
+
+impl Annotations {
+    pub fn start(control: Handle, bar: Option<ProgressBar>) -> Annotations {
+        if !stdin().is_terminal() {
+            thread::spawn(move || read_lines(&control));
+            return Annotations { saved: None };
+        }
+
+        let saved = terminal_mode();
+
+        if let Some(saved) = saved {
+            set_key_mode(&saved);
+            thread::spawn(move || read_keys(&control, bar.as_ref(), &saved));
+        }
+
+        Annotations { saved }
+    }
+}
+
+impl Drop for Annotations {
+    fn drop(&mut self) {
+        if let Some(saved) = self.saved.as_ref() {
+            set_terminal_mode(saved);
+        }
+    }
+}
+
+fn read_lines(control: &Handle) {
+    for line in stdin().lines() {
+        let Ok(line) = line else {
+            return;
+        };
+
+        control.send(Command::Marker(Some(line)));
+    }
+}
+
+fn read_keys(control: &Handle, bar: Option<&ProgressBar>, saved: &libc::termios) {
+    let mut key = [0u8];
+
+    while stdin().read(&mut key).is_ok_and(|count| count == 1) {
+        match key[0] {
+            b'm' => control.send(Command::Marker(None)),
+            b'a' | b'\n' => {
+                if let Some(bar) = bar {
+                    bar.set_draw_target(ProgressDrawTarget::hidden());
+                }
+
+                set_terminal_mode(saved);
+                eprint!("\nAnnotation: ");
+
+                let mut text = String::new();
+                let read = stdin().read_line(&mut text);
+
+                set_key_mode(saved);
+
+                if let Some(bar) = bar {
+                    bar.set_draw_target(ProgressDrawTarget::stderr());
+                }
+
+                if read.is_ok() {
+                    control.send(Command::Marker(Some(text)));
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn terminal_mode() -> Option<libc::termios> {
+    let mut mode = std::mem::MaybeUninit::uninit();
+
+    // SAFETY: tcgetattr initializes `mode` when it succeeds.
+    unsafe {
+        if libc::tcgetattr(libc::STDIN_FILENO, mode.as_mut_ptr()) == 0 {
+            Some(mode.assume_init())
+        } else {
+            None
+        }
+    }
+}
+
+/// Delivers keys immediately without echo, while `CTRL-C` still interrupts.
+fn set_key_mode(saved: &libc::termios) {
+    let mut mode = *saved;
+    mode.c_lflag &= !(libc::ICANON | libc::ECHO);
+    mode.c_cc[libc::VMIN] = 1;
+    mode.c_cc[libc::VTIME] = 0;
+    set_terminal_mode(&mode);
+}
+
+fn set_terminal_mode(mode: &libc::termios) {
+    // SAFETY: `mode` is a valid termios structure.
+    unsafe {
+        libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, mode);
+    }
+}
@@ -1,98 +1,821 @@
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
+use std::thread;
 use std::time::{Duration, Instant};
 
-use anyhow::Result;
+use anyhow::{Context, Result};
+use chrono::prelude::*;
+use chrono::{DurationRound, TimeDelta};
+use clap::ValueEnum;
 
-use crate::csvfile;
+use crate::adaptive::{Adaptive, Detector};
+use crate::alarm::{Alarms, Monitor};
+use crate::control::{Command, Control, Handle, Request};
+use crate::derived::Channel;
+use crate::filter::{self, Filter, Smoother};
 use crate::instrument;
+use crate::integral::Integral;
+use crate::metrics;
+use crate::output::{Column, Footer, Output, Sample, Stop};
+use crate::scaling::Scaling;
 use crate::scpi;
 use crate::status;
+use crate::trigger::{Capturer, Event, Trigger};
+
+/// Idle time after which the connection to the instrument is kept alive.
+const KEEP_ALIVE: Duration = Duration::from_secs(30);
+
+pub struct Settings {
+    pub sample_period: Duration,
+    pub num_samples: u32,
+    pub drop_slow_samples: bool,
+    pub late: Late,
+    pub deadband: Option<Deadband>,
+    pub heartbeat: Option<Duration>,
+    pub adaptive: Option<Adaptive>,
+    pub trigger: Option<Trigger>,
+    pub alarms: Option<Alarms>,
+    pub filters: Vec<Filter>,
+    pub show_filtered: Option<filter::Kind>,
+    pub scaling: Option<Scaling>,
+    pub derived: Vec<Channel>,
+    pub integrals: Vec<Channel>,
+    pub marker_column: bool,
+    pub start_at: Option<DateTime<Local>>,
+    pub align: Option<Align>,
+    pub duration: Option<Duration>,
+    pub stop_at: Option<DateTime<Local>>,
+}
+
+/// Policy for samples, whose planned time already passed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Late {
+    /// Measures immediately and keeps the schedule, so samples follow in a
+    /// burst until the schedule is caught up.
+    CatchUp,
+    /// Skips the missed intervals and waits for the next planned time.
+    Skip,
+    /// Measures immediately and restarts the schedule with this sample.
+    ReAnchor,
+    /// Plans every sample one interval after the previous sample.
+    Fixed,
+}
+
+impl Late {
+    pub fn describe(&self) -> &'static str {
+        match self {
+            Late::CatchUp => "catch up",
+            Late::Skip => "skip to next interval",
+            Late::ReAnchor => "re-anchor schedule",
+            Late::Fixed => "fixed interval from previous sample",
+        }
+    }
+}
+
+/// Change of the reading below which samples are not written.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Deadband {
+    Absolute(f64),
+    /// Fraction of the last written reading.
+    Relative(f64),
+}
+
+impl Deadband {
+    pub fn describe(&self) -> String {
+        match self {
+            Deadband::Absolute(delta) => format!("{delta}"),
+            Deadband::Relative(fraction) => {
+                format!("{} % of last written reading", fraction * 100.0)
+            }
+        }
+    }
+
+    /// Tells whether the reading is within the deadband around the reference.
+    fn contains(&self, reference: f64, reading: f64) -> bool {
+        let band = match self {
+            Deadband::Absolute(delta) => *delta,
+            Deadband::Relative(fraction) => fraction * reference.abs(),
+        };
+
+        (reading - reference).abs() <= band
+    }
+}
+
+/// Boundary the first sample is aligned to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Align {
+    Second,
+    Minute,
+}
+
+impl Align {
+    pub fn describe(&self) -> &'static str {
+        match self {
+            Align::Second => "whole second",
+            Align::Minute => "whole minute",
+        }
+    }
+
+    fn round_up(&self, datetime: DateTime<Local>) -> DateTime<Local> {
+        let unit = match self {
+            Align::Second => TimeDelta::seconds(1),
+            Align::Minute => TimeDelta::minutes(1),
+        };
+
+        datetime.duration_round_up(unit).unwrap_or(datetime)
+    }
+}
+
+/// Additional columns written for the settings, in the order of the values
+/// in `Sample::extra`.
+pub fn columns(settings: &Settings) -> Vec<Column> {
+    let mut columns = Vec::new();
+
+    for filter in &settings.filters {
+        columns.push(Column::new(filter.name(), filter.describe()));
+    }
+
+    if let Some(scaling) = &settings.scaling {
+        columns.push(Column::new(
+            "value",
+            format!("Scaled reading: {}", scaling.formula()),
+        ));
+    }
+
+    for channel in &settings.derived {
+        columns.push(Column::new(
+            &channel.name,
+            format!("Derived channel: {} = {}", channel.name, channel.formula),
+        ));
+    }
+
+    for channel in &settings.integrals {
+        columns.push(Column::new(
+            &channel.name,
+            format!(
+                "Integral of {} over time in hours since first measurement",
+                channel.formula
+            ),
+        ));
+    }
+
+    if settings.marker_column {
+        columns.push(Column::new(
+            "marker",
+            "Number of the marker inserted before this sample",
+        ));
+    }
+
+    columns
+}
 
 pub fn run(
     dmm: &mut scpi::Device,
-    mut output: csvfile::CsvFile,
-    sample_period: Duration,
-    num_samples: u32,
+    output: &mut dyn Output,
+    settings: &Settings,
     bar: status::MyProgressBar,
-    drop_slow_samples: bool,
+    metrics: metrics::Exporter,
+    control: &Control,
 ) -> Result<()> {
-    let term = install_signal_hooks()?;
+    let mut logger = Logger {
+        dmm,
+        output,
+        bar,
+        metrics,
+        control,
+        term: install_signal_hooks()?,
+        sample_period: settings.sample_period,
+        deadline: None,
+        contacted: Instant::now(),
+        stopped_by: None,
+        finished: None,
+        paused: false,
+        markers: 0,
+        marker: None,
+        samples: 0,
+        dropped: 0,
+        failed: 0,
+        late: 0,
+        unchanged: 0,
+        written: None,
+        adaptive: settings.adaptive.map(Detector::new),
+        capturer: settings.trigger.map(Capturer::new),
+        monitor: settings.alarms.clone().map(Monitor::new),
+        filters: settings
+            .filters
+            .iter()
+            .copied()
+            .map(Smoother::new)
+            .collect(),
+        integrals: settings
+            .integrals
+            .iter()
+            .cloned()
+            .map(Integral::new)
+            .collect(),
+    };
+
+    let signals = install_control_signals(control.handle())?;
+
+    let result = logger.measure(settings);
+
+    signals.close();
+
+    let stop = match &result {
+        Err(err) => Stop::Failed(format!("{err:#}")),
+        Ok(()) => match (logger.signal(), logger.stopped_by.take()) {
+            (Some(signal), _) => Stop::Interrupted(signal),
+            (None, Some(source)) => Stop::Requested(source),
+            (None, None) => logger.finished.map_or(Stop::Completed, Stop::Scheduled),
+        },
+    };
+
+    let integrals = logger
+        .integrals
+        .iter()
+        .map(|integral| (integral.channel.name.clone(), integral.total()))
+        .collect::<Vec<_>>();
+
+    let written = logger.output.write_footer(&Footer {
+        finished: Local::now(),
+        samples: logger.samples,
+        dropped: logger.dropped,
+        failed: logger.failed,
+        late: logger.late,
+        unchanged: logger.unchanged,
+        captures: logger.capturer.as_ref().map(Capturer::captures),
+        untriggered: logger.capturer.as_ref().map_or(0, Capturer::untriggered),
+        integrals: &integrals,
+        stop: &stop,
+        statistics: logger.bar.statistics(),
+    });
 
-    let (datetime, started, latency, first_reading) = instrument::read(dmm, 0)?;
+    logger.bar.finish();
 
-    if drop_slow_samples && latency >= sample_period {
-        output.write_comment(format!("0: Latency too high! ({})", latency.as_secs_f64()))?;
-    } else {
-        output.write_reading(0, datetime, 0.0, 0.0, latency.as_secs_f64(), first_reading)?;
+    if let Some(monitor) = logger.monitor.as_mut() {
+        monitor.wait();
     }
 
-    bar.update(first_reading);
+    result.and(written)
+}
+
+enum Wait {
+    Elapsed,
+    /// Logging continues with a new schedule starting now.
+    Rescheduled,
+    Stopped,
+}
+
+/// State of a running measurement, counting the samples for the summary.
+struct Logger<'a> {
+    dmm: &'a mut scpi::Device,
+    output: &'a mut dyn Output,
+    bar: status::MyProgressBar,
+    metrics: metrics::Exporter,
+    control: &'a Control,
+    term: Arc<AtomicUsize>,
+    sample_period: Duration,
+    /// End of the run with the reason noted in the summary.
+    deadline: Option<(Instant, &'static str)>,
+    /// Time of the last communication with the instrument.
+    contacted: Instant,
+    stopped_by: Option<String>,
+    finished: Option<&'static str>,
+    paused: bool,
+    markers: u32,
+    marker: Option<u32>,
+    samples: u32,
+    dropped: u32,
+    failed: u32,
+    late: u32,
+    unchanged: u32,
+    /// Reading and moment of the last written sample.
+    written: Option<(f64, f64)>,
+    adaptive: Option<Detector>,
+    capturer: Option<Capturer>,
+    monitor: Option<Monitor>,
+    filters: Vec<Smoother>,
+    integrals: Vec<Integral>,
+}
+
+impl Logger<'_> {
+    fn measure(&mut self, settings: &Settings) -> Result<()> {
+        let drop_slow_samples = settings.drop_slow_samples;
+
+        let mut start = self.schedule(settings);
+        let mut started = None;
+        let mut anchor: Option<(Instant, u32)> = None;
+        let mut sequence = 0;
+
+        while sequence < settings.num_samples {
+            if self.deadline_reached() {
+                break;
+            }
+
+            let planed = anchor
+                .map(|(instant, first)| instant + (sequence - first) * self.sample_period)
+                .or_else(|| start.take());
 
-    for sequence in 1..num_samples {
-        let planed = started + sequence * sample_period;
-        let now = Instant::now();
+            let mut reanchor = settings.late == Late::Fixed;
 
-        if drop_slow_samples && now >= planed {
-            let delay = (now - planed).as_secs_f64();
-            output.write_comment(format!("{sequence}: Too late! {delay}"))?
-        } else if sleep_until(planed, &term) {
-            let (datetime, moment, latency, reading) = instrument::read(dmm, sequence)?;
+            if let Some(planed) = planed {
+                let now = Instant::now();
 
-            let delay = (moment - planed).as_secs_f64();
-            let moment = (moment - started).as_secs_f64();
+                if drop_slow_samples && now >= planed {
+                    let delay = (now - planed).as_secs_f64();
+                    self.drop_sample(&format!("{sequence}: Too late! {delay}"))?;
+                    sequence += 1;
+                    continue;
+                }
 
-            if drop_slow_samples && latency >= sample_period {
-                output.write_comment(format!(
+                if now > planed {
+                    self.late += 1;
+                    let delay = (now - planed).as_secs_f64();
+
+                    match (settings.late, anchor.as_mut()) {
+                        (Late::Skip, Some((instant, _))) => {
+                            let missed = (delay / self.sample_period.as_secs_f64()).ceil() as u32;
+                            *instant += missed * self.sample_period;
+                            self.output.write_comment(&format!(
+                                "{sequence}: Too late by {delay} seconds, skipped {missed} intervals"
+                            ))?;
+                            continue;
+                        }
+                        (Late::ReAnchor, _) => {
+                            self.output.write_comment(&format!(
+                                "{sequence}: Too late by {delay} seconds, schedule re-anchored"
+                            ))?;
+                            reanchor = true;
+                        }
+                        _ => {}
+                    }
+                }
+
+                match self.wait_until(planed, sequence)? {
+                    Wait::Elapsed => {}
+                    Wait::Rescheduled => {
+                        // Keeps waiting for the scheduled start.
+                        if anchor.is_none() {
+                            start = Some(planed);
+                        }
+                        anchor = None;
+                        continue;
+                    }
+                    Wait::Stopped => break,
+                }
+            }
+
+            let (datetime, measured, latency, reading) = self.read(sequence)?;
+
+            let delay = planed.map_or(0.0, |planed| (measured - planed).as_secs_f64());
+            if reanchor {
+                anchor = Some((measured, sequence));
+            } else {
+                anchor.get_or_insert((planed.unwrap_or(measured), sequence));
+            }
+            let moment = (measured - *started.get_or_insert(measured)).as_secs_f64();
+
+            let computed = self.compute(settings, reading, moment);
+
+            if drop_slow_samples && latency >= self.sample_period {
+                self.drop_sample(&format!(
                     "{sequence}: Latency too high! ({})",
                     latency.as_secs_f64()
-                ))?
+                ))?;
+            } else if self.unchanged(settings, reading, moment) {
+                self.metrics
+                    .update(datetime, latency.as_secs_f64(), reading);
+                self.unchanged += 1;
             } else {
-                output.write_reading(
+                let extra = self.extra(settings, computed);
+                let sample = Sample {
                     sequence,
                     datetime,
                     moment,
                     delay,
-                    latency.as_secs_f64(),
+                    latency: latency.as_secs_f64(),
                     reading,
-                )?;
+                    extra,
+                };
+
+                match self.capturer.as_mut() {
+                    Some(capturer) => {
+                        let events = capturer.add(sample);
+                        self.metrics
+                            .update(datetime, latency.as_secs_f64(), reading);
+
+                        for event in events {
+                            match event {
+                                Event::Comment(comment) => self.output.write_comment(&comment)?,
+                                Event::Sample(sample) => self.write(&sample)?,
+                            }
+                        }
+                    }
+                    None => self.record(&sample)?,
+                }
             }
 
-            bar.update(reading);
-        } else {
-            break;
+            self.check_alarms(sequence, datetime, reading)?;
+            self.bar.update(reading);
+
+            if self.adapt(sequence + 1, moment, reading)? {
+                anchor = Some((measured, sequence));
+            }
+
+            sequence += 1;
         }
+
+        Ok(())
     }
 
-    Ok(())
-}
+    /// Returns the planned time of the first sample, if logging does not
+    /// start immediately, and sets the deadline of the run.
+    fn schedule(&mut self, settings: &Settings) -> Option<Instant> {
+        let mut first = settings.start_at;
 
-fn install_signal_hooks() -> Result<Arc<AtomicBool>> {
-    let term = Arc::new(AtomicBool::new(false));
-    for signal in signal_hook::consts::TERM_SIGNALS {
-        signal_hook::flag::register(*signal, Arc::clone(&term))?;
+        if let Some(align) = settings.align {
+            first = Some(align.round_up(first.unwrap_or_else(Local::now)));
+        }
+
+        if let Some(first) = first {
+            self.bar.set_waiting(&format!(
+                "Waiting for start at {}",
+                first.format("%Y-%m-%d %H:%M:%S")
+            ));
+        }
+
+        let start = first.map_or_else(Instant::now, instant_at);
+
+        let deadlines = [
+            settings
+                .duration
+                .map(|duration| (start + duration, "Duration elapsed")),
+            settings
+                .stop_at
+                .map(|stop_at| (instant_at(stop_at), "Stop time reached")),
+        ];
+
+        self.deadline = deadlines
+            .into_iter()
+            .flatten()
+            .min_by_key(|(deadline, _)| *deadline);
+
+        first.map(|_| start)
     }
-    Ok(term)
-}
 
-fn sleep_until(until: Instant, term: &AtomicBool) -> bool {
-    use std::thread::sleep;
+    fn deadline_reached(&mut self) -> bool {
+        match self.deadline {
+            Some((deadline, reason)) if Instant::now() >= deadline => {
+                self.finished = Some(reason);
+                true
+            }
+            _ => false,
+        }
+    }
 
-    loop {
-        if term.load(Ordering::Relaxed) {
-            return false;
+    /// Waits until the planned time of the next sample, while handling
+    /// signals and commands, keeping the connection to the instrument alive
+    /// and letting the output commit pending writes. A pause lasts until
+    /// resumed, stopped or the deadline.
+    fn wait_until(&mut self, until: Instant, sequence: u32) -> Result<Wait> {
+        use std::thread::sleep;
+
+        loop {
+            if self.term.load(Ordering::Relaxed) != 0 || self.deadline_reached() {
+                return Ok(Wait::Stopped);
+            }
+
+            if self.contacted.elapsed() >= KEEP_ALIVE {
+                self.dmm
+                    .request("*OPC?")
+                    .context("Keeping connection to instrument alive failed")?;
+                self.contacted = Instant::now();
+            }
+
+            while let Some(request) = self.control.try_recv() {
+                if let Some(wait) = self.handle(&request, sequence)? {
+                    return Ok(wait);
+                }
+            }
+
+            self.output.idle()?;
+
+            let now = Instant::now();
+            if !self.paused && now >= until {
+                return Ok(Wait::Elapsed);
+            }
+
+            let remaining = until.saturating_duration_since(now);
+            if self.paused || remaining.as_millis() >= 150 {
+                sleep(Duration::from_millis(100));
+            } else {
+                sleep(remaining);
+            }
+        }
+    }
+
+    /// Executes a command and replies to it, changes are noted as comments.
+    fn handle(&mut self, request: &Request, sequence: u32) -> Result<Option<Wait>> {
+        let mut wait = None;
+
+        match &request.command {
+            Command::Pause if !self.paused => {
+                self.output.write_comment(&format!("{sequence}: Paused"))?;
+                self.set_paused(true);
+            }
+            Command::Resume if self.paused => {
+                self.output.write_comment(&format!("{sequence}: Resumed"))?;
+                self.set_paused(false);
+                wait = Some(Wait::Rescheduled);
+            }
+            Command::Pause | Command::Resume => {}
+            Command::Marker(text) => self.insert_marker(sequence, text.clone())?,
+            Command::Interval(interval) => {
+                self.output.write_comment(&format!(
+                    "{sequence}: Sampling interval changed to {} seconds",
+                    interval.as_secs_f64()
+                ))?;
+                self.sample_period = *interval;
+
+                if !self.paused {
+                    wait = Some(Wait::Rescheduled);
+                }
+            }
+            Command::Rotate => {
+                // Logging continues in the current file, if it is still open.
+                if let Err(err) = self.output.rotate() {
+                    self.bar
+                        .console()
+                        .print(&format!("Rotating output failed: {err:#}"));
+                    request.reply(format!("ERROR {err:#}"));
+                    self.output
+                        .write_comment(&format!("{sequence}: Rotating output failed: {err:#}"))?;
+                    return Ok(None);
+                }
+            }
+            Command::Status => {
+                request.reply(self.status(sequence));
+                return Ok(None);
+            }
+            Command::Statistics => {
+                request.reply(self.statistics());
+                return Ok(None);
+            }
+            Command::Stop(source) => {
+                self.stopped_by = Some(source.clone());
+                wait = Some(Wait::Stopped);
+            }
+        }
+
+        request.reply("OK");
+        Ok(wait)
+    }
+
+    fn status(&self, sequence: u32) -> String {
+        format!(
+            "OK state={} sequence={sequence} samples={} dropped={} failed={} late={} interval={}",
+            if self.paused { "paused" } else { "running" },
+            self.samples,
+            self.dropped,
+            self.failed,
+            self.late,
+            self.sample_period.as_secs_f64()
+        )
+    }
+
+    fn statistics(&self) -> String {
+        let stats = self.bar.statistics();
+
+        if stats.count() == 0 {
+            return "OK count=0".into();
+        }
+
+        format!(
+            "OK count={} mean={} std={} min={} max={} p-p={}",
+            stats.count(),
+            status::precise(stats.mean()),
+            status::precise(stats.std_dev()),
+            status::precise(stats.min()),
+            status::precise(stats.max()),
+            status::precise(stats.peak_to_peak())
+        )
+    }
+
+    /// Writes a numbered marker as comment, which is also noted in the marker
+    /// column of the next sample.
+    fn insert_marker(&mut self, sequence: u32, text: Option<String>) -> Result<()> {
+        self.markers += 1;
+        self.marker = Some(self.markers);
+
+        let time = Local::now().format("%Y-%m-%d %H:%M:%S%.3f");
+        let marker = match text.as_deref().map(str::trim) {
+            Some(text) if !text.is_empty() => {
+                format!("{sequence}: Marker {} at {time}: {text}", self.markers)
+            }
+            _ => format!("{sequence}: Marker {} at {time}", self.markers),
+        };
+
+        self.output.write_comment(&marker)
+    }
+
+    /// Values of the filter, scaled, derived and integral columns. Filters
+    /// and integrals are updated with every reading, also the ones not
+    /// written.
+    fn compute(&mut self, settings: &Settings, reading: f64, moment: f64) -> Vec<Option<f64>> {
+        let mut computed = Vec::new();
+
+        for smoother in &mut self.filters {
+            let value = smoother.add(reading);
+
+            if settings.show_filtered == Some(smoother.filter.kind()) {
+                self.bar.set_filtered(value, smoother.filter.name());
+            }
+
+            computed.push(Some(value));
+        }
+
+        // Variables of the derived channels as given by `derived::variables`.
+        let mut values = vec![reading, reading, moment];
+
+        if let Some(scaling) = &settings.scaling {
+            let value = scaling.apply(reading);
+            values.push(value);
+            computed.push(Some(value));
+        }
+
+        for channel in &settings.derived {
+            let value = channel.evaluate(&values);
+            values.push(value);
+            computed.push(Some(value).filter(|value| value.is_finite()));
+        }
+
+        for integral in &mut self.integrals {
+            let value = integral.channel.evaluate(&values);
+            computed.push(Some(status::rounded(integral.add(moment, value))));
+        }
+
+        computed
+    }
+
+    /// Appends the columns following the computed ones.
+    fn extra(&mut self, settings: &Settings, mut extra: Vec<Option<f64>>) -> Vec<Option<f64>> {
+        let marker = self.marker.take();
+        if settings.marker_column {
+            extra.push(marker.map(f64::from));
+        }
+
+        extra
+    }
+
+    /// Pauses or resumes logging, the integrals do not extend across the
+    /// pause.
+    fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+        self.control.set_paused(paused);
+
+        for integral in &mut self.integrals {
+            integral.interrupt();
         }
+    }
+
+    fn read(&mut self, sequence: u32) -> Result<(DateTime<Local>, Instant, Duration, f64)> {
+        self.contacted = Instant::now();
+        instrument::read(self.dmm, sequence).inspect_err(|_| {
+            self.metrics.error();
+            self.failed += 1;
+        })
+    }
+
+    /// Writes raised and cleared alarms as comments, notifies about them and
+    /// highlights the progress bar while an alarm is active.
+    fn check_alarms(
+        &mut self,
+        sequence: u32,
+        datetime: DateTime<Local>,
+        reading: f64,
+    ) -> Result<()> {
+        let Some(monitor) = self.monitor.as_mut() else {
+            return Ok(());
+        };
 
-        let now = Instant::now();
-        if now >= until {
-            return true;
+        let events = monitor.check(reading);
+        if events.is_empty() {
+            return Ok(());
         }
 
-        let remaining = until - now;
-        if remaining.as_millis() >= 150 {
-            sleep(Duration::from_millis(100));
-        } else {
-            sleep(remaining);
+        self.bar.set_alarm(monitor.is_active());
+
+        for event in events {
+            self.output.write_comment(&event.describe(sequence))?;
+            monitor.notify(&event.to_json(sequence, datetime));
+
+            if event.raised && monitor.alarms().beep {
+                self.dmm
+                    .send("SYST:BEEP")
+                    .context("Beeping instrument on alarm failed")?;
+            }
         }
+
+        Ok(())
+    }
+
+    /// Switches between the slow and fast sampling interval depending on the
+    /// rate of change, returns whether the interval changed.
+    fn adapt(&mut self, sequence: u32, moment: f64, reading: f64) -> Result<bool> {
+        let Some(change) = self
+            .adaptive
+            .as_mut()
+            .and_then(|adaptive| adaptive.update(moment, reading))
+        else {
+            return Ok(false);
+        };
+
+        self.sample_period = change.interval;
+        self.output.write_comment(&format!(
+            "{sequence}: Sampling interval changed to {} seconds, rate of change {} per second",
+            change.interval.as_secs_f64(),
+            status::precise(change.rate)
+        ))?;
+
+        Ok(true)
     }
+
+    /// Tells whether the reading is within the deadband of the last written
+    /// reading and no heartbeat is due, so the sample is not written.
+    fn unchanged(&self, settings: &Settings, reading: f64, moment: f64) -> bool {
+        let (Some(deadband), Some((reference, written))) = (settings.deadband, self.written) else {
+            return false;
+        };
+
+        let heartbeat = settings
+            .heartbeat
+            .is_some_and(|heartbeat| moment - written >= heartbeat.as_secs_f64());
+
+        !heartbeat && deadband.contains(reference, reading)
+    }
+
+    fn record(&mut self, sample: &Sample) -> Result<()> {
+        self.write(sample)?;
+        self.metrics
+            .update(sample.datetime, sample.latency, sample.reading);
+        Ok(())
+    }
+
+    fn write(&mut self, sample: &Sample) -> Result<()> {
+        self.output.write_reading(sample)?;
+        self.samples += 1;
+        self.written = Some((sample.reading, sample.moment));
+        Ok(())
+    }
+
+    fn drop_sample(&mut self, comment: &str) -> Result<()> {
+        self.output.write_comment(comment)?;
+        self.metrics.dropped();
+        self.dropped += 1;
+        Ok(())
+    }
+
+    fn signal(&self) -> Option<&'static str> {
+        match self.term.load(Ordering::Relaxed) {
+            0 => None,
+            signal => Some(signal_hook::low_level::signal_name(signal as i32).unwrap_or("signal")),
+        }
+    }
+}
+
+/// Converts a wall clock time into an instant, past times into now.
+fn instant_at(datetime: DateTime<Local>) -> Instant {
+    let remaining = (datetime - Local::now()).to_std().unwrap_or_default();
+    Instant::now() + remaining
+}
+
+/// Forwards `SIGUSR1` as pause, `SIGUSR2` as resume and `SIGHUP` as rotate
+/// command, the way daemons are controlled.
+fn install_control_signals(control: Handle) -> Result<signal_hook::iterator::Handle> {
+    use signal_hook::consts::{SIGHUP, SIGUSR1, SIGUSR2};
+
+    let mut signals = signal_hook::iterator::Signals::new([SIGUSR1, SIGUSR2, SIGHUP])?;
+    let handle = signals.handle();
+
+    thread::spawn(move || {
+        for signal in signals.forever() {
+            control.send(match signal {
+                SIGUSR1 => Command::Pause,
+                SIGUSR2 => Command::Resume,
+                _ => Command::Rotate,
+            });
+        }
+    });
+
+    Ok(handle)
+}
+
+/// Registers the termination signals, the returned value is the number of
+/// the last received signal or zero.
+fn install_signal_hooks() -> Result<Arc<AtomicUsize>> {
+    let term = Arc::new(AtomicUsize::new(0));
+    for signal in signal_hook::consts::TERM_SIGNALS {
+        signal_hook::flag::register_usize(*signal, Arc::clone(&term), *signal as usize)?;
+    }
+    Ok(term)
 }
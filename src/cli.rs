@@ -1,5 +1,17 @@
+use crate::adaptive::Adaptive;
+use crate::alarm::Alarms;
+use crate::app::{Align, Deadband, Late};
+use crate::compress::Compression;
+use crate::derived::{self, Channel};
+use crate::filter::{self, Filter};
+use crate::mqtt::Qos;
+use crate::output::{self, Format};
+use crate::scaling::Scaling;
 use crate::scpi::DEFAULT_PORT;
-use anyhow::{bail, Result};
+use crate::trigger::Trigger;
+use anyhow::{bail, Context, Result};
+use chrono::prelude::*;
+use chrono::TimeDelta;
 use clap::Parser;
 use std::time::Duration;
 
@@ -32,6 +44,38 @@ pub struct Cli {
     )]
     num_samples: Option<u32>,
 
+    #[arg(
+        help = "Start logging at DATETIME, e.g. 2026-10-17T06:00 or 06:00",
+        long,
+        value_name = "DATETIME",
+        value_parser = parse_datetime
+    )]
+    start_at: Option<DateTime<Local>>,
+
+    #[arg(
+        help = "Align the first sample to a whole second or minute",
+        long,
+        value_name = "BOUNDARY",
+        value_enum
+    )]
+    align: Option<Align>,
+
+    #[arg(
+        help = "Stop logging after DURATION, e.g. 90s, 15m, 72h, 2d or 1h30m",
+        long,
+        value_name = "DURATION",
+        value_parser = parse_duration
+    )]
+    duration: Option<Duration>,
+
+    #[arg(
+        help = "Stop logging at DATETIME",
+        long,
+        value_name = "DATETIME",
+        value_parser = parse_datetime
+    )]
+    stop_at: Option<DateTime<Local>>,
+
     #[arg(
         help = "Switch off instruments display during logging",
         long,
@@ -51,6 +95,213 @@ pub struct Cli {
     #[arg(help = "Drop delayed samples or samples with high latency", long)]
     drop_slow_samples: bool,
 
+    #[arg(
+        help = "Policy for samples whose planned time already passed",
+        long,
+        value_name = "POLICY",
+        value_enum,
+        default_value = "catch-up"
+    )]
+    late: Late,
+
+    #[arg(
+        help = "Write a sample only when the reading changed by more than DELTA, or by a percentage like 0.5%",
+        long,
+        value_name = "DELTA",
+        value_parser = parse_deadband
+    )]
+    deadband: Option<Deadband>,
+
+    #[arg(
+        help = "Write a sample at least every DURATION despite the deadband",
+        long,
+        value_name = "DURATION",
+        value_parser = parse_duration,
+        requires = "deadband"
+    )]
+    heartbeat: Option<Duration>,
+
+    #[arg(
+        help = "Switch to the sampling interval SECONDS while the readings change fast",
+        long,
+        value_name = "SECONDS",
+        requires = "fast_above"
+    )]
+    fast_interval: Option<f64>,
+
+    #[arg(
+        help = "Rate of change per second above which the fast interval is used",
+        long,
+        value_name = "RATE",
+        requires = "fast_interval"
+    )]
+    fast_above: Option<f64>,
+
+    #[arg(
+        help = "Rate of change per second below which the interval switches back [default: half of --fast-above]",
+        long,
+        value_name = "RATE",
+        requires = "fast_interval"
+    )]
+    slow_below: Option<f64>,
+
+    #[arg(
+        help = "Number of recent readings for the rate of change",
+        long,
+        value_name = "COUNT",
+        default_value_t = 5
+    )]
+    activity_window: usize,
+
+    #[arg(
+        help = "Capture samples around readings crossing LEVEL",
+        long,
+        value_name = "LEVEL",
+        allow_hyphen_values = true
+    )]
+    trigger_level: Option<f64>,
+
+    #[arg(
+        help = "Capture samples around readings leaving the range LOW:HIGH",
+        long,
+        value_name = "LOW:HIGH",
+        allow_hyphen_values = true,
+        value_parser = parse_window
+    )]
+    trigger_window: Option<(f64, f64)>,
+
+    #[arg(
+        help = "Capture samples around readings changing by more than DELTA",
+        long,
+        value_name = "DELTA"
+    )]
+    trigger_slope: Option<f64>,
+
+    #[arg(
+        help = "Number of samples captured before the trigger",
+        long,
+        value_name = "COUNT",
+        default_value_t = 10
+    )]
+    pre_trigger: usize,
+
+    #[arg(
+        help = "Number of samples captured after the trigger",
+        long,
+        value_name = "COUNT",
+        default_value_t = 10
+    )]
+    post_trigger: usize,
+
+    #[arg(
+        help = "Raise an alarm when a reading is above LIMIT",
+        long,
+        value_name = "LIMIT",
+        allow_hyphen_values = true
+    )]
+    upper_limit: Option<f64>,
+
+    #[arg(
+        help = "Raise an alarm when a reading is below LIMIT",
+        long,
+        value_name = "LIMIT",
+        allow_hyphen_values = true
+    )]
+    lower_limit: Option<f64>,
+
+    #[arg(
+        help = "Distance a reading must return within the limit to clear the alarm [default: 0]",
+        long,
+        value_name = "DELTA"
+    )]
+    hysteresis: Option<f64>,
+
+    #[arg(
+        help = "Run shell COMMAND on alarms, with the event as JSON in DMM_ALARM and on stdin",
+        long,
+        value_name = "COMMAND"
+    )]
+    alarm_exec: Option<String>,
+
+    #[arg(help = "Post alarms as JSON to URL", long, value_name = "URL")]
+    alarm_url: Option<String>,
+
+    #[arg(help = "Beep instrument when an alarm is raised", long)]
+    alarm_beep: bool,
+
+    #[arg(
+        help = "Add a value column scaling the reading by FACTOR",
+        long,
+        value_name = "FACTOR",
+        allow_hyphen_values = true,
+        conflicts_with_all = ["calibrate"]
+    )]
+    scale: Option<f64>,
+
+    #[arg(
+        help = "Add OFFSET to the scaled reading in the value column",
+        long,
+        value_name = "OFFSET",
+        allow_hyphen_values = true,
+        conflicts_with_all = ["calibrate"]
+    )]
+    offset: Option<f64>,
+
+    #[arg(
+        help = "Scale through two points of reading and value, e.g. 0.004:0,0.02:10",
+        long,
+        value_name = "RAW:VALUE,RAW:VALUE",
+        allow_hyphen_values = true,
+        value_parser = parse_calibration
+    )]
+    calibrate: Option<[(f64, f64); 2]>,
+
+    #[arg(help = "Unit of the value column", long, value_name = "UNIT")]
+    unit: Option<String>,
+
+    #[arg(
+        help = "Add a column computed per sample, e.g. 'power = reading * reading / 50'",
+        long,
+        value_name = "NAME=EXPRESSION"
+    )]
+    derive: Vec<String>,
+
+    #[arg(
+        help = "Add a column integrating an expression over time in hours, e.g. 'charge = reading'",
+        long,
+        value_name = "NAME=EXPRESSION"
+    )]
+    integrate: Vec<String>,
+
+    #[arg(
+        help = "Add a column with the moving average of the last COUNT readings",
+        long,
+        value_name = "COUNT"
+    )]
+    moving_average: Option<usize>,
+
+    #[arg(
+        help = "Add a column with the running median of the last COUNT readings",
+        long,
+        value_name = "COUNT"
+    )]
+    median: Option<usize>,
+
+    #[arg(
+        help = "Add a column with the exponential moving average, ALPHA is the weight of the newest reading",
+        long,
+        value_name = "ALPHA"
+    )]
+    ema: Option<f64>,
+
+    #[arg(
+        help = "Show the filtered value instead of the reading in the progress bar",
+        long,
+        value_name = "FILTER",
+        value_enum
+    )]
+    show_filtered: Option<filter::Kind>,
+
     #[arg(
         help = "Configures instrument for voltage measurement",
         short = 'U',
@@ -163,6 +414,114 @@ pub struct Cli {
     )]
     message_from: Option<String>,
 
+    #[arg(
+        help = "Format of FILE [default: by file extension, otherwise csv]",
+        long,
+        value_name = "FORMAT",
+        value_enum
+    )]
+    format: Option<Format>,
+
+    #[arg(
+        help = "Additional output as [FORMAT:]FILE, where FILE - is stdout",
+        short,
+        long = "output",
+        value_name = "OUTPUT"
+    )]
+    outputs: Vec<String>,
+
+    #[arg(
+        help = "Compress FILE [default: by file extension .gz or .zst]",
+        long,
+        value_name = "METHOD",
+        value_enum
+    )]
+    compress: Option<Compression>,
+
+    #[arg(
+        help = "Number of samples per row group in Parquet files",
+        long,
+        value_name = "COUNT",
+        default_value_t = 10000
+    )]
+    row_group_size: usize,
+
+    #[arg(
+        help = "API token for pushing to InfluxDB",
+        long,
+        value_name = "TOKEN",
+        env = "INFLUX_TOKEN",
+        hide_env_values = true
+    )]
+    influx_token: Option<String>,
+
+    #[arg(
+        help = "MQTT topic for samples",
+        long,
+        value_name = "TEMPLATE",
+        default_value = "lab/{model}/{serial}/reading"
+    )]
+    mqtt_topic: String,
+
+    #[arg(
+        help = "MQTT topic for the retained header and comments",
+        long,
+        value_name = "TEMPLATE",
+        default_value = "lab/{model}/{serial}/metadata"
+    )]
+    mqtt_meta_topic: String,
+
+    #[arg(
+        help = "MQTT quality of service",
+        long,
+        value_name = "QOS",
+        value_enum,
+        default_value = "1"
+    )]
+    mqtt_qos: Qos,
+
+    #[arg(
+        help = "Number of MQTT messages to buffer while the broker is unreachable",
+        long,
+        value_name = "COUNT",
+        default_value_t = 10000
+    )]
+    mqtt_buffer: usize,
+
+    #[arg(
+        help = "Serve Prometheus metrics on ADDRESS, e.g. 0.0.0.0:9100",
+        long,
+        value_name = "ADDRESS"
+    )]
+    metrics: Option<String>,
+
+    #[arg(
+        help = "Serve a web dashboard with a live chart on ADDRESS, e.g. 0.0.0.0:8080",
+        long,
+        value_name = "ADDRESS"
+    )]
+    web: Option<String>,
+
+    #[arg(
+        help = "Accept commands for the running measurement on a Unix socket at PATH",
+        long,
+        value_name = "PATH"
+    )]
+    control: Option<String>,
+
+    #[arg(help = "Show full-screen terminal UI with live charts", long)]
+    tui: bool,
+
+    #[arg(help = "Add a column with the number of the preceding marker", long)]
+    marker_column: bool,
+
+    #[arg(
+        help = "Show statistics over the last COUNT readings instead of all readings",
+        long,
+        value_name = "COUNT"
+    )]
+    stats_window: Option<usize>,
+
     #[arg(help = "Beep instrument when logging finished", long)]
     beep: bool,
 
@@ -183,10 +542,10 @@ pub struct Cli {
     host: String,
 
     #[arg(
-        help = "Filename to save the CSV lines into.\nIf omitted, lines are written to stdout.",
+        help = "Filename to save the lines into, or InfluxDB or MQTT URL to send to.\nIf omitted, lines are written to stdout.",
         value_name = "FILE"
     )]
-    output: Option<String>,
+    file: Option<String>,
 }
 
 impl Cli {
@@ -203,6 +562,87 @@ impl Cli {
             bail!("Number of samples 0 is not allowed");
         }
 
+        if self.stats_window == Some(0) {
+            bail!("Statistics window of 0 readings is not allowed");
+        }
+
+        if self.row_group_size == 0 {
+            bail!("Row group size 0 is not allowed");
+        }
+
+        if self.fast_interval.is_some_and(|interval| interval <= 0.0) {
+            bail!("Fast sampling interval must be greater than 0 seconds");
+        }
+
+        if let Some(adaptive) = self.adaptive() {
+            if adaptive.slow_below > adaptive.fast_above {
+                bail!("--slow-below must not be greater than --fast-above");
+            }
+        }
+
+        if self.deadband.is_some() && self.trigger().is_some() {
+            bail!("Deadband and trigger can not be used together");
+        }
+
+        if self.upper_limit.is_none()
+            && self.lower_limit.is_none()
+            && (self.hysteresis.is_some()
+                || self.alarm_exec.is_some()
+                || self.alarm_url.is_some()
+                || self.alarm_beep)
+        {
+            bail!("Alarms require --upper-limit or --lower-limit");
+        }
+
+        if let (Some(lower), Some(upper)) = (self.lower_limit, self.upper_limit) {
+            if lower > upper {
+                bail!("Lower limit {lower} is greater than upper limit {upper}");
+            }
+        }
+
+        if self.hysteresis.is_some_and(|hysteresis| hysteresis < 0.0) {
+            bail!("Hysteresis must not be negative");
+        }
+
+        if self.moving_average.is_some_and(|count| count < 2)
+            || self.median.is_some_and(|count| count < 2)
+        {
+            bail!("Filters need at least 2 readings");
+        }
+
+        if self.ema.is_some_and(|alpha| !(alpha > 0.0 && alpha <= 1.0)) {
+            bail!("Weight of exponential moving average must be greater than 0 and at most 1");
+        }
+
+        if let Some(kind) = self.show_filtered {
+            if !self.filters().iter().any(|filter| filter.kind() == kind) {
+                bail!("Filter to show is not set up");
+            }
+        }
+
+        self.scaling()?;
+        self.derived()?;
+        self.integrals()?;
+
+        if self.activity_window < 2 {
+            bail!("Activity window needs at least 2 readings");
+        }
+
+        let now = Local::now();
+
+        if let Some(start_at) = self.start_at.filter(|start_at| *start_at <= now) {
+            bail!("Start time {} is in the past", format_datetime(start_at));
+        }
+
+        if let Some(stop_at) = self.stop_at {
+            if stop_at <= self.start_at.unwrap_or(now) {
+                bail!(
+                    "Stop time {} is not after the start",
+                    format_datetime(stop_at)
+                );
+            }
+        }
+
         Ok(self)
     }
 
@@ -210,6 +650,126 @@ impl Cli {
         self.drop_slow_samples
     }
 
+    pub fn late(&self) -> Late {
+        self.late
+    }
+
+    pub fn deadband(&self) -> Option<Deadband> {
+        self.deadband
+    }
+
+    pub fn heartbeat(&self) -> Option<Duration> {
+        self.heartbeat
+    }
+
+    pub fn adaptive(&self) -> Option<Adaptive> {
+        let fast_above = self.fast_above?;
+
+        Some(Adaptive {
+            slow_interval: self.sample_period(),
+            fast_interval: Duration::from_secs_f64(self.fast_interval?),
+            fast_above,
+            slow_below: self.slow_below.unwrap_or(fast_above / 2.0),
+            window: self.activity_window,
+        })
+    }
+
+    pub fn trigger(&self) -> Option<Trigger> {
+        if self.trigger_level.is_none()
+            && self.trigger_window.is_none()
+            && self.trigger_slope.is_none()
+        {
+            return None;
+        }
+
+        Some(Trigger {
+            level: self.trigger_level,
+            window: self.trigger_window,
+            slope: self.trigger_slope.map(f64::abs),
+            pre: self.pre_trigger,
+            post: self.post_trigger,
+        })
+    }
+
+    pub fn alarms(&self) -> Option<Alarms> {
+        if self.upper_limit.is_none() && self.lower_limit.is_none() {
+            return None;
+        }
+
+        Some(Alarms {
+            upper: self.upper_limit,
+            lower: self.lower_limit,
+            hysteresis: self.hysteresis.unwrap_or(0.0),
+            exec: self.alarm_exec.clone(),
+            url: self.alarm_url.clone(),
+            beep: self.alarm_beep,
+        })
+    }
+
+    pub fn filters(&self) -> Vec<Filter> {
+        [
+            self.moving_average.map(Filter::Average),
+            self.median.map(Filter::Median),
+            self.ema.map(Filter::Ema),
+        ]
+        .into_iter()
+        .flatten()
+        .collect()
+    }
+
+    pub fn show_filtered(&self) -> Option<filter::Kind> {
+        self.show_filtered
+    }
+
+    /// Scaling for the value column, if any scaling option is given.
+    pub fn scaling(&self) -> Result<Option<Scaling>> {
+        let unit = self.unit.clone();
+
+        if let Some(points) = self.calibrate {
+            return Scaling::calibrated(points, unit).map(Some);
+        }
+
+        if self.scale.is_none() && self.offset.is_none() && unit.is_none() {
+            return Ok(None);
+        }
+
+        Ok(Some(Scaling {
+            gain: self.scale.unwrap_or(1.0),
+            offset: self.offset.unwrap_or(0.0),
+            unit,
+            points: None,
+        }))
+    }
+
+    /// Derived channels in the order of definition.
+    pub fn derived(&self) -> Result<Vec<Channel>> {
+        let scaled = self.scaling()?.is_some();
+        Channel::parse_all(&self.derive, derived::variables(scaled))
+    }
+
+    /// Expressions to integrate, which can use the derived channels.
+    pub fn integrals(&self) -> Result<Vec<Channel>> {
+        let scaled = self.scaling()?.is_some();
+        let mut variables = derived::variables(scaled);
+        variables.extend(self.derived()?.into_iter().map(|channel| channel.name));
+
+        let mut names = Vec::new();
+
+        self.integrate
+            .iter()
+            .map(|definition| {
+                let channel = Channel::parse(definition, &variables)?;
+
+                if names.contains(&channel.name) {
+                    bail!("Integral `{}` is defined twice", channel.name);
+                }
+                names.push(channel.name.clone());
+
+                Ok(channel)
+            })
+            .collect()
+    }
+
     pub fn reset(&self) -> bool {
         self.reset
     }
@@ -234,14 +794,97 @@ impl Cli {
         self.port
     }
 
-    pub fn output(&self) -> Option<&str> {
-        self.output.as_deref()
+    pub fn destinations(&self) -> Result<Vec<output::Destination>> {
+        let mut destinations = Vec::new();
+
+        if self.file.is_some() || self.outputs.is_empty() {
+            destinations.push(output::Destination {
+                filename: self.file.clone(),
+                format: self.format(),
+                compression: self.compression(),
+            });
+        }
+
+        for spec in &self.outputs {
+            destinations.push(output::Destination::parse(spec)?);
+        }
+
+        Ok(destinations)
+    }
+
+    fn format(&self) -> Format {
+        self.format.unwrap_or_else(|| {
+            self.file
+                .as_deref()
+                .map(Format::from_filename)
+                .unwrap_or(Format::Csv)
+        })
+    }
+
+    fn compression(&self) -> Compression {
+        self.compress.unwrap_or_else(|| {
+            self.file
+                .as_deref()
+                .map(Compression::from_filename)
+                .unwrap_or(Compression::None)
+        })
+    }
+
+    pub fn output_options(&self) -> output::Options {
+        output::Options {
+            row_group_size: self.row_group_size,
+            influx_token: self.influx_token.clone(),
+            mqtt_topic: self.mqtt_topic.clone(),
+            mqtt_meta_topic: self.mqtt_meta_topic.clone(),
+            mqtt_qos: self.mqtt_qos,
+            mqtt_buffer: self.mqtt_buffer,
+        }
+    }
+
+    pub fn web(&self) -> Option<&str> {
+        self.web.as_deref()
+    }
+
+    pub fn control(&self) -> Option<&str> {
+        self.control.as_deref()
+    }
+
+    pub fn tui(&self) -> bool {
+        self.tui
+    }
+
+    pub fn marker_column(&self) -> bool {
+        self.marker_column
+    }
+
+    pub fn stats_window(&self) -> Option<usize> {
+        self.stats_window
+    }
+
+    pub fn metrics(&self) -> Option<&str> {
+        self.metrics.as_deref()
     }
 
     pub fn sample_period(&self) -> Duration {
         Duration::from_secs_f64(self.rate.map(|f| 1.0 / f).unwrap_or(self.interval))
     }
 
+    pub fn start_at(&self) -> Option<DateTime<Local>> {
+        self.start_at
+    }
+
+    pub fn align(&self) -> Option<Align> {
+        self.align
+    }
+
+    pub fn duration(&self) -> Option<Duration> {
+        self.duration
+    }
+
+    pub fn stop_at(&self) -> Option<DateTime<Local>> {
+        self.stop_at
+    }
+
     pub fn num_samples(&self) -> u32 {
         self.num_samples.unwrap_or(u32::MAX)
     }
@@ -263,6 +906,56 @@ impl Cli {
             infos.push(("Drop slow samples".into(), "ON".into()));
         }
 
+        if self.late != Late::CatchUp {
+            infos.push(("Late sample policy".into(), self.late.describe().into()));
+        }
+
+        if let Some(deadband) = self.deadband {
+            infos.push(("Deadband".into(), deadband.describe()));
+        }
+
+        if let Some(adaptive) = self.adaptive() {
+            infos.push(("Fast interval".into(), adaptive.describe()));
+        }
+
+        if let Some(trigger) = self.trigger() {
+            infos.push(("Trigger".into(), trigger.describe()));
+        }
+
+        if let Some(alarms) = self.alarms() {
+            infos.push(("Alarm".into(), alarms.describe()));
+        }
+
+        if let Ok(Some(scaling)) = self.scaling() {
+            infos.push(("Scaling".into(), scaling.describe()));
+        }
+
+        if let Some(heartbeat) = self.heartbeat {
+            infos.push((
+                "Heartbeat".into(),
+                format!("{} seconds", heartbeat.as_secs_f64()),
+            ));
+        }
+
+        if let Some(start_at) = self.start_at {
+            infos.push(("Start at".into(), format_datetime(start_at)));
+        }
+
+        if let Some(align) = self.align {
+            infos.push(("Align first sample".into(), align.describe().into()));
+        }
+
+        if let Some(duration) = self.duration {
+            infos.push((
+                "Duration".into(),
+                format!("{} seconds", duration.as_secs_f64()),
+            ));
+        }
+
+        if let Some(stop_at) = self.stop_at {
+            infos.push(("Stop at".into(), format_datetime(stop_at)));
+        }
+
         if self.display_off {
             infos.push(("Display".into(), "OFF".into()));
         } else if self.display_text.is_some() {
@@ -294,9 +987,27 @@ impl Cli {
             infos.push(("Reset instrument".into(), "ON".into()));
         }
 
+        if self.compression() != Compression::None {
+            infos.push(("Compression".into(), self.compression().describe().into()));
+        }
+
         infos
     }
 
+    pub fn function(&self) -> Option<String> {
+        let dc_ac = if self.ac { "AC" } else { "DC" };
+
+        if self.voltage.is_some() {
+            Some(format!("VOLT:{dc_ac}"))
+        } else if self.current.is_some() {
+            Some(format!("CURR:{dc_ac}"))
+        } else if self.resistance.is_some() {
+            Some(if self.four { "FRES" } else { "RES" }.into())
+        } else {
+            None
+        }
+    }
+
     pub fn configuration_commands(&self) -> Vec<String> {
         let mut configs = Vec::new();
 
@@ -362,3 +1073,164 @@ impl Cli {
         unconfigs
     }
 }
+
+fn format_datetime(datetime: DateTime<Local>) -> String {
+    datetime.format("%Y-%m-%d %H:%M:%S").to_string()
+}
+
+/// Parses a local date and time, or the next occurrence of a time of day.
+fn parse_datetime(value: &str) -> Result<DateTime<Local>> {
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(value) {
+        return Ok(datetime.with_timezone(&Local));
+    }
+
+    let formats = [
+        "%Y-%m-%dT%H:%M:%S",
+        "%Y-%m-%dT%H:%M",
+        "%Y-%m-%d %H:%M:%S",
+        "%Y-%m-%d %H:%M",
+    ];
+
+    let datetime = formats
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+        .or_else(|| {
+            let time = ["%H:%M:%S", "%H:%M"]
+                .iter()
+                .find_map(|format| NaiveTime::parse_from_str(value, format).ok())?;
+            let now = Local::now().naive_local();
+            let today = now.date().and_time(time);
+
+            Some(if today > now {
+                today
+            } else {
+                today + TimeDelta::days(1)
+            })
+        });
+
+    let Some(datetime) = datetime else {
+        bail!("Invalid date and time `{value}`, expected e.g. 2026-10-17T06:00");
+    };
+
+    datetime
+        .and_local_timezone(Local)
+        .earliest()
+        .with_context(|| format!("Local time `{value}` does not exist"))
+}
+
+/// Parses a range `LOW:HIGH`.
+fn parse_window(value: &str) -> Result<(f64, f64)> {
+    let Some((low, high)) = value.split_once(':') else {
+        bail!("Invalid range `{value}`, expected LOW:HIGH");
+    };
+
+    let low = low
+        .trim()
+        .parse::<f64>()
+        .with_context(|| format!("Invalid lower limit in `{value}`"))?;
+    let high = high
+        .trim()
+        .parse::<f64>()
+        .with_context(|| format!("Invalid upper limit in `{value}`"))?;
+
+    if low > high {
+        bail!("Lower limit is greater than upper limit in `{value}`");
+    }
+
+    Ok((low, high))
+}
+
+/// Parses two calibration points `RAW:VALUE,RAW:VALUE`.
+fn parse_calibration(value: &str) -> Result<[(f64, f64); 2]> {
+    let points = value
+        .split(',')
+        .map(|point| {
+            let (raw, scaled) = point.split_once(':').with_context(|| {
+                format!("Invalid calibration point `{point}`, expected RAW:VALUE")
+            })?;
+            let raw = raw
+                .trim()
+                .parse::<f64>()
+                .with_context(|| format!("Invalid reading in calibration point `{point}`"))?;
+            let scaled = scaled
+                .trim()
+                .parse::<f64>()
+                .with_context(|| format!("Invalid value in calibration point `{point}`"))?;
+            Ok((raw, scaled))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let Ok(points) = <[(f64, f64); 2]>::try_from(points) else {
+        bail!("Invalid calibration `{value}`, expected two points RAW:VALUE,RAW:VALUE");
+    };
+
+    Ok(points)
+}
+
+/// Parses an absolute deadband like `0.001` or a relative one like `0.5%`.
+fn parse_deadband(value: &str) -> Result<Deadband> {
+    let (number, relative) = match value.trim().strip_suffix('%') {
+        Some(number) => (number, true),
+        None => (value.trim(), false),
+    };
+
+    let number = number
+        .trim()
+        .parse::<f64>()
+        .with_context(|| format!("Invalid deadband `{value}`"))?;
+
+    if !number.is_finite() || number < 0.0 {
+        bail!("Deadband must not be negative");
+    }
+
+    Ok(if relative {
+        Deadband::Relative(number / 100.0)
+    } else {
+        Deadband::Absolute(number)
+    })
+}
+
+/// Parses a duration like `90s`, `15m`, `72h`, `2d` or `1h30m`, plain
+/// numbers are seconds.
+fn parse_duration(value: &str) -> Result<Duration> {
+    let seconds = match value.trim().parse::<f64>() {
+        Ok(seconds) => seconds,
+        Err(_) => {
+            let mut seconds = 0.0;
+            let mut rest = value.trim();
+
+            while !rest.is_empty() {
+                let number_end = rest
+                    .find(|c: char| !c.is_ascii_digit() && c != '.')
+                    .unwrap_or(rest.len());
+                let (number, tail) = rest.split_at(number_end);
+                let unit_end = tail
+                    .find(|c: char| c.is_ascii_digit() || c == '.')
+                    .unwrap_or(tail.len());
+                let (unit, tail) = tail.split_at(unit_end);
+
+                let number = number
+                    .parse::<f64>()
+                    .with_context(|| format!("Invalid duration `{value}`"))?;
+
+                let factor = match unit.trim() {
+                    "s" => 1.0,
+                    "m" | "min" => 60.0,
+                    "h" => 3600.0,
+                    "d" => 86400.0,
+                    _ => bail!("Invalid unit in duration `{value}`, expected s, m, h or d"),
+                };
+
+                seconds += number * factor;
+                rest = tail.trim_start();
+            }
+
+            seconds
+        }
+    };
+
+    match Duration::try_from_secs_f64(seconds) {
+        Ok(duration) if !duration.is_zero() => Ok(duration),
+        _ => bail!("Duration must be greater than 0 seconds"),
+    }
+}
@@ -0,0 +1,118 @@
+use std::io::{Result, Write};
+use std::time::{Duration, Instant};
+
+use clap::ValueEnum;
+use flate2::write::GzEncoder;
+
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Compression {
+    None,
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    pub fn from_filename(filename: &str) -> Compression {
+        if filename.ends_with(".gz") {
+            Compression::Gzip
+        } else if filename.ends_with(".zst") {
+            Compression::Zstd
+        } else {
+            Compression::None
+        }
+    }
+
+    pub fn describe(&self) -> &'static str {
+        match self {
+            Compression::None => "none",
+            Compression::Gzip => "gzip",
+            Compression::Zstd => "zstd",
+        }
+    }
+}
+
+pub fn writer(output: Box<dyn Write>, compression: Compression) -> Result<Box<dyn Write>> {
+    Ok(match compression {
+        Compression::None => output,
+        Compression::Gzip => Box::new(Compressor::new(Encoder::Gzip(GzEncoder::new(
+            output,
+            flate2::Compression::default(),
+        )))),
+        Compression::Zstd => Box::new(Compressor::new(Encoder::Zstd(zstd::Encoder::new(
+            output, 0,
+        )?))),
+    })
+}
+
+enum Encoder {
+    Gzip(GzEncoder<Box<dyn Write>>),
+    Zstd(zstd::Encoder<'static, Box<dyn Write>>),
+}
+
+/// Compressing writer which flushes at most once per `FLUSH_INTERVAL`.
+///
+/// A gzip stream gets a sync flush, a zstd stream ends the current block
+/// within its single frame, so the history is kept for compressing the next
+/// lines. Either way a killed process leaves a file which decompresses up to
+/// the last flush.
+struct Compressor {
+    encoder: Encoder,
+    flushed: Instant,
+}
+
+impl Compressor {
+    fn new(encoder: Encoder) -> Compressor {
+        Compressor {
+            encoder,
+            flushed: Instant::now(),
+        }
+    }
+
+    fn end_block(&mut self) -> Result<()> {
+        match &mut self.encoder {
+            Encoder::Gzip(encoder) => encoder.flush(),
+            Encoder::Zstd(encoder) => encoder.flush(),
+        }
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        match &mut self.encoder {
+            Encoder::Gzip(encoder) => {
+                encoder.try_finish()?;
+                encoder.get_mut().flush()
+            }
+            Encoder::Zstd(encoder) => {
+                encoder.do_finish()?;
+                encoder.get_mut().flush()
+            }
+        }
+    }
+}
+
+impl Write for Compressor {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        match &mut self.encoder {
+            Encoder::Gzip(encoder) => encoder.write(buf),
+            Encoder::Zstd(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        if self.flushed.elapsed() >= FLUSH_INTERVAL {
+            self.flushed = Instant::now();
+            self.end_block()
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl Drop for Compressor {
+    fn drop(&mut self) {
+        if let Err(err) = self.finish() {
+            eprintln!("Finishing compressed output failed: {err}");
+        }
+    }
+}
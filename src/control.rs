@@ -0,0 +1,109 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+
+const REPLY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Commands to control a running measurement.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Pause,
+    Resume,
+    /// Inserts a numbered marker comment, with an optional text.
+    Marker(Option<String>),
+    /// Changes the sampling interval.
+    Interval(Duration),
+    /// Renames the output files and continues in new files.
+    Rotate,
+    Status,
+    Statistics,
+    /// Stops logging, the text tells who requested it.
+    Stop(String),
+}
+
+/// Command together with the way back to the sender waiting for the reply.
+pub struct Request {
+    pub command: Command,
+    reply: Option<Sender<String>>,
+}
+
+impl Request {
+    pub fn reply(&self, text: impl Into<String>) {
+        if let Some(reply) = self.reply.as_ref() {
+            let _ = reply.send(text.into());
+        }
+    }
+}
+
+/// Receives commands from any number of handles, for example the terminal
+/// UI, and publishes the state of the measurement back to them.
+pub struct Control {
+    sender: Sender<Request>,
+    receiver: Receiver<Request>,
+    paused: Arc<AtomicBool>,
+}
+
+impl Control {
+    pub fn new() -> Control {
+        let (sender, receiver) = mpsc::channel();
+
+        Control {
+            sender,
+            receiver,
+            paused: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    pub fn handle(&self) -> Handle {
+        Handle {
+            sender: self.sender.clone(),
+            paused: Arc::clone(&self.paused),
+        }
+    }
+
+    pub fn try_recv(&self) -> Option<Request> {
+        self.receiver.try_recv().ok()
+    }
+
+    pub fn set_paused(&self, paused: bool) {
+        self.paused.store(paused, Ordering::Relaxed);
+    }
+}
+
+#[derive(Clone)]
+pub struct Handle {
+    sender: Sender<Request>,
+    paused: Arc<AtomicBool>,
+}
+
+impl Handle {
+    pub fn send(&self, command: Command) {
+        let _ = self.sender.send(Request {
+            command,
+            reply: None,
+        });
+    }
+
+    /// Sends the command and waits for the reply.
+    pub fn request(&self, command: Command) -> Result<String> {
+        let (reply, receiver) = mpsc::channel();
+
+        self.sender
+            .send(Request {
+                command,
+                reply: Some(reply),
+            })
+            .map_err(|_| anyhow!("Logging finished"))?;
+
+        receiver
+            .recv_timeout(REPLY_TIMEOUT)
+            .map_err(|_| anyhow!("No reply from logger"))
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Relaxed)
+    }
+}
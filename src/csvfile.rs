@@ -1,10 +1,10 @@
-use std::fs::File;
-use std::io::{stdout, BufWriter, Write};
+use std::io::{BufWriter, Write};
 
 use anyhow::{Context, Ok, Result};
 use chrono::prelude::*;
 use unicode_segmentation::UnicodeSegmentation;
 
+use crate::output::{Column, Footer, Header, Output, Sample};
 use crate::scpi::Identification;
 
 const PKG_VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -16,10 +16,7 @@ pub struct CsvFile {
 }
 
 impl CsvFile {
-    pub fn stdout() -> CsvFile {
-        let output: BufWriter<Box<dyn Write>> = BufWriter::new(Box::new(stdout()));
-        let filename = None;
-
+    pub fn new(filename: Option<String>, output: BufWriter<Box<dyn Write>>) -> CsvFile {
         CsvFile {
             filename,
             output,
@@ -27,93 +24,6 @@ impl CsvFile {
         }
     }
 
-    pub fn create_new(filename: &str) -> Result<CsvFile> {
-        let output: BufWriter<Box<dyn Write>> = BufWriter::new(Box::new(
-            File::options()
-                .create_new(true)
-                .write(true)
-                .open(filename)
-                .with_context(|| format!("Creating CSV file '{filename}' failed"))?,
-        ));
-
-        let filename = Some(filename.into());
-
-        Ok(CsvFile {
-            filename,
-            output,
-            width: 0,
-        })
-    }
-
-    pub fn write_header(
-        &mut self,
-        settings: &Vec<(String, String)>,
-        ident: &Identification,
-        user_message: Option<&str>,
-    ) -> Result<()> {
-        self.ensure_width(
-            settings
-                .iter()
-                .map(|(label, _)| label.len())
-                .max()
-                .unwrap_or(0),
-        );
-
-        self.ensure_width("Manufacturer".len());
-
-        (|| {
-            self.write_title()?;
-            self.write_user_message(user_message)?;
-            self.write_settings_description(settings)?;
-            self.write_instrument_identification(ident)?;
-            self.write_column_description()?;
-            self.write_column_headers()?;
-            self.output.flush()?;
-            Ok(())
-        })()
-        .with_context(|| {
-            if let Some(filename) = self.filename.as_deref() {
-                format!("Writing headers to CSV file '{filename}' failed")
-            } else {
-                "Writing CSV headers to stdout failed".into()
-            }
-        })
-    }
-
-    pub fn write_reading(
-        &mut self,
-        sequence: u32,
-        datetime: DateTime<Local>,
-        moment: f64,
-        delay: f64,
-        latency: f64,
-        reading: f64,
-    ) -> Result<()> {
-        (|| {
-            let date = datetime.format("%Y-%m-%d");
-            let time = datetime.format("%H:%M:%S.%3f");
-
-            writeln!(
-                self.output,
-                "{sequence},{date},{time},{moment:.4},{delay:.4},{latency:.4},{reading}"
-            )?;
-
-            self.output.flush()
-        })()
-        .with_context(|| {
-            if let Some(filename) = self.filename.as_deref() {
-                format!("Writing data to CSV file '{filename}' failed")
-            } else {
-                "Writing data to stdout failed".into()
-            }
-        })
-    }
-
-    pub fn write_comment<T: std::fmt::Display>(&mut self, comment: T) -> Result<()> {
-        writeln!(self.output, "# {comment}")?;
-        Ok(())
-    }
-
     fn ensure_width(&mut self, width: usize) {
         self.width = std::cmp::max(self.width, width);
     }
@@ -153,7 +63,7 @@ impl CsvFile {
         Ok(())
     }
 
-    fn write_settings_description(&mut self, description: &Vec<(String, String)>) -> Result<()> {
+    fn write_settings_description(&mut self, description: &[(String, String)]) -> Result<()> {
         if !description.is_empty() {
             writeln!(self.output, "# Settings")?;
             writeln!(self.output, "# --------")?;
@@ -182,7 +92,7 @@ impl CsvFile {
         Ok(())
     }
 
-    fn write_column_description(&mut self) -> Result<()> {
+    fn write_column_description(&mut self, columns: &[Column]) -> Result<()> {
         writeln!(self.output, "# Fields")?;
         writeln!(self.output, "# ------")?;
 
@@ -209,16 +119,26 @@ impl CsvFile {
 
         self.write_label_value("reading", "Measured value returned from instrument")?;
 
+        for column in columns {
+            self.write_label_value(&column.name, &column.description)?;
+        }
+
         writeln!(self.output, "#")?;
 
         Ok(())
     }
 
-    pub fn write_column_headers(&mut self) -> Result<()> {
-        writeln!(
+    pub fn write_column_headers(&mut self, columns: &[Column]) -> Result<()> {
+        write!(
             self.output,
             "sequence,date,time,moment,delay,latency,reading"
         )?;
+
+        for column in columns {
+            write!(self.output, ",{}", column.name)?;
+        }
+
+        writeln!(self.output)?;
         Ok(())
     }
 
@@ -232,3 +152,130 @@ impl CsvFile {
         Ok(())
     }
 }
+
+impl Output for CsvFile {
+    fn write_header(&mut self, header: &Header) -> Result<()> {
+        self.ensure_width(
+            header
+                .settings
+                .iter()
+                .map(|(label, _)| label.len())
+                .max()
+                .unwrap_or(0),
+        );
+
+        self.ensure_width("Manufacturer".len());
+
+        self.ensure_width(
+            header
+                .columns
+                .iter()
+                .map(|column| column.name.len())
+                .max()
+                .unwrap_or(0),
+        );
+
+        (|| {
+            self.write_title()?;
+            self.write_user_message(header.message)?;
+            self.write_settings_description(header.settings)?;
+            self.write_instrument_identification(header.identification)?;
+            self.write_column_description(header.columns)?;
+            self.write_column_headers(header.columns)?;
+            self.output.flush()?;
+            Ok(())
+        })()
+        .with_context(|| {
+            if let Some(filename) = self.filename.as_deref() {
+                format!("Writing headers to CSV file '{filename}' failed")
+            } else {
+                "Writing CSV headers to stdout failed".into()
+            }
+        })
+    }
+
+    fn write_reading(&mut self, sample: &Sample) -> Result<()> {
+        (|| {
+            let Sample {
+                sequence,
+                datetime,
+                moment,
+                delay,
+                latency,
+                reading,
+                extra,
+            } = sample;
+
+            let date = datetime.format("%Y-%m-%d");
+            let time = datetime.format("%H:%M:%S.%3f");
+
+            write!(
+                self.output,
+                "{sequence},{date},{time},{moment:.4},{delay:.4},{latency:.4},{reading}"
+            )?;
+
+            for value in extra {
+                match value {
+                    Some(value) => write!(self.output, ",{value}")?,
+                    None => write!(self.output, ",")?,
+                }
+            }
+
+            writeln!(self.output)?;
+
+            self.output.flush()
+        })()
+        .with_context(|| {
+            if let Some(filename) = self.filename.as_deref() {
+                format!("Writing data to CSV file '{filename}' failed")
+            } else {
+                "Writing data to stdout failed".into()
+            }
+        })
+    }
+
+    fn write_comment(&mut self, comment: &str) -> Result<()> {
+        // Comments are also written while waiting, e.g. when paused.
+        writeln!(self.output, "# {comment}")
+            .and_then(|_| self.output.flush())
+            .with_context(|| {
+                if let Some(filename) = self.filename.as_deref() {
+                    format!("Writing comment to CSV file '{filename}' failed")
+                } else {
+                    "Writing comment to stdout failed".into()
+                }
+            })
+    }
+
+    fn write_footer(&mut self, footer: &Footer) -> Result<()> {
+        let summary = footer.describe();
+
+        self.ensure_width(
+            summary
+                .iter()
+                .map(|(label, _)| label.len())
+                .max()
+                .unwrap_or(0),
+        );
+
+        (|| {
+            writeln!(self.output, "#")?;
+            writeln!(self.output, "# Summary")?;
+            writeln!(self.output, "# -------")?;
+
+            for (label, value) in &summary {
+                self.write_label_value(label, value)?;
+            }
+
+            self.output.flush()?;
+            Ok(())
+        })()
+        .with_context(|| {
+            if let Some(filename) = self.filename.as_deref() {
+                format!("Writing summary to CSV file '{filename}' failed")
+            } else {
+                "Writing CSV summary to stdout failed".into()
+            }
+        })
+    }
+}
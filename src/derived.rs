@@ -0,0 +1,578 @@
+use anyhow::{bail, Context, Result};
+
+use crate::status;
+
+/// Names of the columns, which can not be used for derived channels.
+const RESERVED: &[&str] = &[
+    "sequence", "date", "time", "datetime", "moment", "delay", "latency", "reading", "value",
+    "marker", "type", "ch1", "average", "median", "ema",
+];
+
+/// Variables available to all derived channels, the values are passed to
+/// `Channel::evaluate` in this order followed by the derived channels. With
+/// a single channel read, `ch1` is another name for `reading`.
+pub fn variables(scaled: bool) -> Vec<String> {
+    let mut variables = vec!["reading".into(), "ch1".into(), "moment".into()];
+
+    if scaled {
+        variables.push("value".into());
+    }
+
+    variables
+}
+
+/// Column computed per sample from an expression, e.g. `power = reading * 2.5`.
+#[derive(Debug, Clone)]
+pub struct Channel {
+    pub name: String,
+    pub formula: String,
+    expression: Expression,
+}
+
+impl Channel {
+    /// Parses the definition `NAME = EXPRESSION`, which can refer to the
+    /// given variables.
+    pub fn parse(definition: &str, variables: &[String]) -> Result<Channel> {
+        let Some((name, formula)) = definition.split_once('=') else {
+            bail!("Invalid derived channel `{definition}`, expected NAME = EXPRESSION");
+        };
+
+        let name = name.trim();
+        let formula = formula.trim();
+
+        if !is_identifier(name) {
+            bail!("Invalid name `{name}` of derived channel, expected letters, digits and _");
+        }
+
+        if RESERVED.contains(&name) || Function::from_name(name).is_some() {
+            bail!("Name `{name}` of derived channel is reserved");
+        }
+
+        if variables.iter().any(|variable| variable == name) {
+            bail!("Derived channel `{name}` is defined twice");
+        }
+
+        let expression = Parser::new(formula, variables)
+            .parse()
+            .with_context(|| format!("Invalid expression of derived channel `{name}`"))?;
+
+        Ok(Channel {
+            name: name.into(),
+            formula: formula.into(),
+            expression,
+        })
+    }
+
+    /// Parses all definitions, each can refer to the channels before it.
+    pub fn parse_all(definitions: &[String], mut variables: Vec<String>) -> Result<Vec<Channel>> {
+        let mut channels = Vec::new();
+
+        for definition in definitions {
+            let channel = Channel::parse(definition, &variables)?;
+            variables.push(channel.name.clone());
+            channels.push(channel);
+        }
+
+        Ok(channels)
+    }
+
+    /// Evaluates the expression with the values of the variables in the
+    /// order given when parsing, rounded like the other computed columns.
+    pub fn evaluate(&self, values: &[f64]) -> f64 {
+        status::rounded(self.expression.evaluate(values))
+    }
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+
+    chars
+        .next()
+        .is_some_and(|first| first.is_ascii_alphabetic() || first == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Function {
+    Abs,
+    Sqrt,
+    Exp,
+    Ln,
+    Log10,
+    Min,
+    Max,
+    /// Temperature in degrees Celsius of a thermistor with the resistance and
+    /// the coefficients A, B and C of the Steinhart-Hart equation.
+    Steinhart,
+}
+
+impl Function {
+    fn from_name(name: &str) -> Option<Function> {
+        Some(match name {
+            "abs" => Function::Abs,
+            "sqrt" => Function::Sqrt,
+            "exp" => Function::Exp,
+            "ln" => Function::Ln,
+            "log10" => Function::Log10,
+            "min" => Function::Min,
+            "max" => Function::Max,
+            "steinhart" => Function::Steinhart,
+            _ => return None,
+        })
+    }
+
+    fn arity(&self) -> usize {
+        match self {
+            Function::Min | Function::Max => 2,
+            Function::Steinhart => 4,
+            _ => 1,
+        }
+    }
+
+    fn apply(&self, args: &[f64]) -> f64 {
+        match (self, args) {
+            (Function::Abs, [x]) => x.abs(),
+            (Function::Sqrt, [x]) => x.sqrt(),
+            (Function::Exp, [x]) => x.exp(),
+            (Function::Ln, [x]) => x.ln(),
+            (Function::Log10, [x]) => x.log10(),
+            (Function::Min, [x, y]) => x.min(*y),
+            (Function::Max, [x, y]) => x.max(*y),
+            (Function::Steinhart, [r, a, b, c]) => {
+                let ln_r = r.ln();
+                1.0 / (a + b * ln_r + c * ln_r.powi(3)) - 273.15
+            }
+            _ => f64::NAN,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Operator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Power,
+}
+
+#[derive(Debug, Clone)]
+enum Expression {
+    Number(f64),
+    /// Index of the variable.
+    Variable(usize),
+    Negate(Box<Expression>),
+    Binary(Operator, Box<Expression>, Box<Expression>),
+    Call(Function, Vec<Expression>),
+}
+
+impl Expression {
+    fn evaluate(&self, values: &[f64]) -> f64 {
+        match self {
+            Expression::Number(number) => *number,
+            Expression::Variable(index) => values.get(*index).copied().unwrap_or(f64::NAN),
+            Expression::Negate(operand) => -operand.evaluate(values),
+            Expression::Binary(operator, left, right) => {
+                let left = left.evaluate(values);
+                let right = right.evaluate(values);
+
+                match operator {
+                    Operator::Add => left + right,
+                    Operator::Subtract => left - right,
+                    Operator::Multiply => left * right,
+                    Operator::Divide => left / right,
+                    Operator::Power => left.powf(right),
+                }
+            }
+            Expression::Call(function, args) => {
+                let args = args
+                    .iter()
+                    .map(|arg| arg.evaluate(values))
+                    .collect::<Vec<_>>();
+                function.apply(&args)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Identifier(String),
+    Symbol(char),
+}
+
+/// Recursive descent parser for arithmetic expressions with the usual
+/// precedence, `^` binding strongest and to the right.
+struct Parser<'a> {
+    tokens: Vec<(usize, Token)>,
+    position: usize,
+    source: &'a str,
+    variables: &'a [String],
+}
+
+impl<'a> Parser<'a> {
+    fn new(source: &'a str, variables: &'a [String]) -> Parser<'a> {
+        Parser {
+            tokens: Vec::new(),
+            position: 0,
+            source,
+            variables,
+        }
+    }
+
+    fn parse(mut self) -> Result<Expression> {
+        self.tokens = tokenize(self.source)?;
+
+        if self.tokens.is_empty() {
+            bail!("Empty expression");
+        }
+
+        let expression = self.sum()?;
+
+        if let Some((column, token)) = self.tokens.get(self.position) {
+            bail!("Unexpected {} at column {}", describe(token), column + 1);
+        }
+
+        Ok(expression)
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position).map(|(_, token)| token)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.peek().cloned();
+        self.position += 1;
+        token
+    }
+
+    fn accept(&mut self, symbol: char) -> bool {
+        if self.peek() == Some(&Token::Symbol(symbol)) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, symbol: char) -> Result<()> {
+        if self.accept(symbol) {
+            return Ok(());
+        }
+
+        match self.tokens.get(self.position) {
+            Some((column, token)) => bail!(
+                "Expected `{symbol}` instead of {} at column {}",
+                describe(token),
+                column + 1
+            ),
+            None => bail!("Expected `{symbol}` at end of expression"),
+        }
+    }
+
+    fn sum(&mut self) -> Result<Expression> {
+        let mut left = self.product()?;
+
+        loop {
+            let operator = if self.accept('+') {
+                Operator::Add
+            } else if self.accept('-') {
+                Operator::Subtract
+            } else {
+                return Ok(left);
+            };
+
+            left = Expression::Binary(operator, Box::new(left), Box::new(self.product()?));
+        }
+    }
+
+    fn product(&mut self) -> Result<Expression> {
+        let mut left = self.unary()?;
+
+        loop {
+            let operator = if self.accept('*') {
+                Operator::Multiply
+            } else if self.accept('/') {
+                Operator::Divide
+            } else {
+                return Ok(left);
+            };
+
+            left = Expression::Binary(operator, Box::new(left), Box::new(self.unary()?));
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expression> {
+        if self.accept('-') {
+            return Ok(Expression::Negate(Box::new(self.unary()?)));
+        }
+
+        if self.accept('+') {
+            return self.unary();
+        }
+
+        self.power()
+    }
+
+    fn power(&mut self) -> Result<Expression> {
+        let base = self.primary()?;
+
+        if self.accept('^') {
+            let exponent = self.unary()?;
+            return Ok(Expression::Binary(
+                Operator::Power,
+                Box::new(base),
+                Box::new(exponent),
+            ));
+        }
+
+        Ok(base)
+    }
+
+    fn primary(&mut self) -> Result<Expression> {
+        let column = self.tokens.get(self.position).map(|(column, _)| column + 1);
+
+        match self.next() {
+            Some(Token::Number(number)) => Ok(Expression::Number(number)),
+            Some(Token::Symbol('(')) => {
+                let expression = self.sum()?;
+                self.expect(')')?;
+                Ok(expression)
+            }
+            Some(Token::Identifier(name)) if self.peek() == Some(&Token::Symbol('(')) => {
+                self.call(&name)
+            }
+            Some(Token::Identifier(name)) => {
+                match self.variables.iter().position(|variable| *variable == name) {
+                    Some(index) => Ok(Expression::Variable(index)),
+                    None => bail!(
+                        "Unknown variable `{name}`, available are {}",
+                        self.variables.join(", ")
+                    ),
+                }
+            }
+            Some(token) => bail!(
+                "Unexpected {} at column {}",
+                describe(&token),
+                column.unwrap_or_default()
+            ),
+            None => bail!("Unexpected end of expression"),
+        }
+    }
+
+    fn call(&mut self, name: &str) -> Result<Expression> {
+        let Some(function) = Function::from_name(name) else {
+            bail!("Unknown function `{name}`");
+        };
+
+        self.expect('(')?;
+
+        let mut args = Vec::new();
+
+        if !self.accept(')') {
+            loop {
+                args.push(self.sum()?);
+
+                if self.accept(')') {
+                    break;
+                }
+
+                self.expect(',')?;
+            }
+        }
+
+        if args.len() != function.arity() {
+            bail!(
+                "Function `{name}` takes {} arguments, but {} given",
+                function.arity(),
+                args.len()
+            );
+        }
+
+        Ok(Expression::Call(function, args))
+    }
+}
+
+fn describe(token: &Token) -> String {
+    match token {
+        Token::Number(number) => format!("number {number}"),
+        Token::Identifier(name) => format!("`{name}`"),
+        Token::Symbol(symbol) => format!("`{symbol}`"),
+    }
+}
+
+/// Splits the source into tokens with their column.
+fn tokenize(source: &str) -> Result<Vec<(usize, Token)>> {
+    let mut tokens = Vec::new();
+    let mut chars = source.char_indices().peekable();
+
+    while let Some(&(start, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c.is_ascii_digit() || c == '.' {
+            let mut end = start;
+            let mut previous = c;
+
+            while let Some(&(index, c)) = chars.peek() {
+                let exponent_sign = (c == '+' || c == '-') && matches!(previous, 'e' | 'E');
+
+                if !(c.is_ascii_alphanumeric() || c == '.' || exponent_sign) {
+                    break;
+                }
+
+                end = index + c.len_utf8();
+                previous = c;
+                chars.next();
+            }
+
+            let number = &source[start..end];
+            let number = number
+                .parse::<f64>()
+                .with_context(|| format!("Invalid number `{number}` at column {}", start + 1))?;
+            tokens.push((start, Token::Number(number)));
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let mut end = start;
+
+            while let Some(&(index, c)) = chars.peek() {
+                if !(c.is_ascii_alphanumeric() || c == '_') {
+                    break;
+                }
+
+                end = index + c.len_utf8();
+                chars.next();
+            }
+
+            tokens.push((start, Token::Identifier(source[start..end].into())));
+        } else if "+-*/^(),".contains(c) {
+            tokens.push((start, Token::Symbol(c)));
+            chars.next();
+        } else {
+            bail!("Unexpected character `{c}` at column {}", start + 1);
+        }
+    }
+
+    Ok(tokens)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn variables() -> Vec<String> {
+        vec!["x".into(), "y".into()]
+    }
+
+    /// Evaluates the formula with `x = 2` and `y = 3`.
+    fn evaluate(formula: &str) -> f64 {
+        Channel::parse(&format!("c = {formula}"), &variables())
+            .unwrap()
+            .evaluate(&[2.0, 3.0])
+    }
+
+    /// Message of the error parsing the definition, including its causes.
+    fn error(definition: &str) -> String {
+        format!(
+            "{:#}",
+            Channel::parse(definition, &variables()).unwrap_err()
+        )
+    }
+
+    #[test]
+    fn precedence() {
+        assert_eq!(evaluate("1 + 2 * 3"), 7.0);
+        assert_eq!(evaluate("(1 + 2) * 3"), 9.0);
+        assert_eq!(evaluate("10 - 4 - 3"), 3.0);
+        assert_eq!(evaluate("8 / 4 / 2"), 1.0);
+        assert_eq!(evaluate("2 * 3 ^ 2"), 18.0);
+        assert_eq!(evaluate("x * y + 1"), 7.0);
+    }
+
+    #[test]
+    fn power_is_right_associative() {
+        assert_eq!(evaluate("2 ^ 3 ^ 2"), 512.0);
+        assert_eq!(evaluate("(2 ^ 3) ^ 2"), 64.0);
+    }
+
+    #[test]
+    fn unary_minus() {
+        assert_eq!(evaluate("-2 ^ 2"), -4.0);
+        assert_eq!(evaluate("(-2) ^ 2"), 4.0);
+        assert_eq!(evaluate("2 ^ -1"), 0.5);
+        assert_eq!(evaluate("--x"), 2.0);
+        assert_eq!(evaluate("y - -x"), 5.0);
+        assert_eq!(evaluate("+x"), 2.0);
+    }
+
+    #[test]
+    fn number_literals() {
+        assert_eq!(evaluate("1e-3"), 0.001);
+        assert_eq!(evaluate("2.5E+2"), 250.0);
+        assert_eq!(evaluate("1e3 - 1"), 999.0);
+        assert_eq!(evaluate(".5"), 0.5);
+        assert!(error("c = 1e").contains("Invalid number `1e`"));
+        assert!(error("c = 1.2.3").contains("Invalid number `1.2.3`"));
+    }
+
+    #[test]
+    fn functions() {
+        assert_eq!(evaluate("min(x, y)"), 2.0);
+        assert_eq!(evaluate("max(x, y)"), 3.0);
+        assert_eq!(evaluate("sqrt(16)"), 4.0);
+        assert_eq!(evaluate("abs(-x)"), 2.0);
+        assert_eq!(evaluate("log10(1000)"), 3.0);
+        assert_eq!(evaluate("ln(exp(x))"), 2.0);
+
+        let temperature = evaluate("steinhart(10000, 1.129148e-3, 2.34125e-4, 8.76741e-8)");
+        assert!((temperature - 25.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn arity() {
+        assert!(error("c = min(1)").contains("Function `min` takes 2 arguments, but 1 given"));
+        assert!(error("c = sqrt(1, 2)").contains("Function `sqrt` takes 1 arguments, but 2 given"));
+        assert!(error("c = steinhart(x)").contains("takes 4 arguments"));
+        assert!(error("c = abs()").contains("takes 1 arguments, but 0 given"));
+    }
+
+    #[test]
+    fn unknown_names() {
+        assert!(error("c = x * z").contains("Unknown variable `z`, available are x, y"));
+        assert!(error("c = foo(x)").contains("Unknown function `foo`"));
+    }
+
+    #[test]
+    fn syntax_errors() {
+        assert!(error("c = ").contains("Empty expression"));
+        assert!(error("c = (x + 1").contains("Expected `)` at end of expression"));
+        assert!(error("c = x +* 2").contains("Unexpected `*` at column 4"));
+        assert!(error("c = x 2").contains("Unexpected number 2 at column 3"));
+        assert!(error("c = x $ 2").contains("Unexpected character `$` at column 3"));
+        assert!(error("c = x +").contains("Unexpected end of expression"));
+    }
+
+    #[test]
+    fn names() {
+        assert!(error("x + 1").contains("expected NAME = EXPRESSION"));
+        assert!(error("2c = 1").contains("Invalid name `2c`"));
+        assert!(error("reading = 1").contains("Name `reading` of derived channel is reserved"));
+        assert!(error("sqrt = 1").contains("is reserved"));
+        assert!(error("x = 1").contains("Derived channel `x` is defined twice"));
+    }
+
+    #[test]
+    fn channels_use_previous_channels() {
+        let definitions = ["a = x * y".to_string(), "b = a + 1".to_string()];
+        let channels = Channel::parse_all(&definitions, variables()).unwrap();
+
+        assert_eq!(channels[1].evaluate(&[2.0, 3.0, 6.0]), 7.0);
+        assert!(Channel::parse_all(&["a = b".into(), "b = 1".into()], variables()).is_err());
+    }
+
+    #[test]
+    fn results_are_rounded() {
+        assert_eq!(evaluate("0.1 + 0.2"), 0.3);
+        assert!(evaluate("x / 0").is_infinite());
+        assert!(evaluate("sqrt(-x)").is_nan());
+    }
+}
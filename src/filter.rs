@@ -0,0 +1,112 @@
+use std::collections::VecDeque;
+
+use clap::ValueEnum;
+
+use crate::status;
+
+/// Smoothing of noisy readings, written as additional column.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Filter {
+    /// Mean of the last readings.
+    Average(usize),
+    /// Median of the last readings.
+    Median(usize),
+    /// Exponential moving average with the weight of the newest reading.
+    Ema(f64),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Kind {
+    Average,
+    Median,
+    Ema,
+}
+
+impl Filter {
+    pub fn kind(&self) -> Kind {
+        match self {
+            Filter::Average(_) => Kind::Average,
+            Filter::Median(_) => Kind::Median,
+            Filter::Ema(_) => Kind::Ema,
+        }
+    }
+
+    /// Name of the column.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Filter::Average(_) => "average",
+            Filter::Median(_) => "median",
+            Filter::Ema(_) => "ema",
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            Filter::Average(count) => format!("Moving average of the last {count} readings"),
+            Filter::Median(count) => format!("Running median of the last {count} readings"),
+            Filter::Ema(alpha) => {
+                format!("Exponential moving average with weight {alpha} of the newest reading")
+            }
+        }
+    }
+}
+
+/// Running state of a filter.
+pub struct Smoother {
+    pub filter: Filter,
+    recent: VecDeque<f64>,
+    ema: Option<f64>,
+}
+
+impl Smoother {
+    pub fn new(filter: Filter) -> Smoother {
+        let capacity = match filter {
+            Filter::Average(count) | Filter::Median(count) => count,
+            Filter::Ema(_) => 0,
+        };
+
+        Smoother {
+            filter,
+            recent: VecDeque::with_capacity(capacity),
+            ema: None,
+        }
+    }
+
+    /// Adds the reading and returns the filtered value. Until the window is
+    /// filled, the value is computed over the readings so far.
+    pub fn add(&mut self, reading: f64) -> f64 {
+        match self.filter {
+            Filter::Average(count) => {
+                self.push(count, reading);
+                status::rounded(self.recent.iter().sum::<f64>() / self.recent.len() as f64)
+            }
+            Filter::Median(count) => {
+                self.push(count, reading);
+
+                let mut sorted = self.recent.iter().copied().collect::<Vec<_>>();
+                sorted.sort_by(f64::total_cmp);
+
+                let middle = sorted.len() / 2;
+                if sorted.len() % 2 == 0 {
+                    status::rounded((sorted[middle - 1] + sorted[middle]) / 2.0)
+                } else {
+                    sorted[middle]
+                }
+            }
+            Filter::Ema(alpha) => {
+                let ema = self
+                    .ema
+                    .map_or(reading, |ema| ema + alpha * (reading - ema));
+                self.ema = Some(ema);
+                status::rounded(ema)
+            }
+        }
+    }
+
+    fn push(&mut self, count: usize, reading: f64) {
+        if self.recent.len() == count {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(reading);
+    }
+}
@@ -0,0 +1,331 @@
+use std::collections::VecDeque;
+use std::io::{BufWriter, Write};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use chrono::prelude::*;
+
+use crate::output::{Header, Output, Sample};
+
+const BATCH_SIZE: usize = 5000;
+const BATCH_INTERVAL: Duration = Duration::from_secs(1);
+const MAX_BUFFERED: usize = 1_000_000;
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+const FINAL_PUSH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Formats samples and comments as InfluxDB line protocol.
+///
+/// Samples become points of the measurement `dmm` tagged with the instrument
+/// model, serial number and measurement function. Comments become points of
+/// the measurement `dmm_comment` with the same tags.
+struct LineFormatter {
+    tags: String,
+    columns: Vec<String>,
+}
+
+impl LineFormatter {
+    fn new() -> LineFormatter {
+        LineFormatter {
+            tags: String::new(),
+            columns: Vec::new(),
+        }
+    }
+
+    fn set_tags(&mut self, header: &Header) {
+        let ident = header.identification;
+
+        let mut tags = vec![
+            ("manufacturer", ident.manufacturer.as_str()),
+            ("model", ident.model.as_str()),
+            ("serial", ident.serial.as_str()),
+        ];
+
+        if let Some(function) = header.function {
+            tags.push(("function", function));
+        }
+
+        self.tags = tags
+            .into_iter()
+            .filter(|(_, value)| !value.is_empty())
+            .map(|(key, value)| format!(",{key}={}", escape_tag(value)))
+            .collect();
+
+        self.columns = header
+            .columns
+            .iter()
+            .map(|column| escape_tag(&column.name))
+            .collect();
+    }
+
+    fn sample(&self, sample: &Sample) -> String {
+        let extra = self
+            .columns
+            .iter()
+            .zip(&sample.extra)
+            .filter_map(|(key, value)| value.map(|value| format!(",{key}={value:?}")))
+            .collect::<String>();
+
+        format!(
+            "dmm{} reading={:?},latency={:?},delay={:?},moment={:?},sequence={}u{extra} {}",
+            self.tags,
+            sample.reading,
+            sample.latency,
+            sample.delay,
+            sample.moment,
+            sample.sequence,
+            timestamp(&sample.datetime),
+        )
+    }
+
+    fn comment(&self, comment: &str) -> String {
+        format!(
+            "dmm_comment{} text=\"{}\" {}",
+            self.tags,
+            escape_string(comment),
+            timestamp(&Local::now()),
+        )
+    }
+}
+
+fn timestamp(datetime: &DateTime<Local>) -> i64 {
+    datetime.timestamp_nanos_opt().unwrap_or_default()
+}
+
+fn escape_tag(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace(',', "\\,")
+        .replace('=', "\\=")
+        .replace(' ', "\\ ")
+}
+
+fn escape_string(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Writes line protocol to a file or stdout.
+pub struct InfluxFile {
+    filename: Option<String>,
+    output: BufWriter<Box<dyn Write>>,
+    formatter: LineFormatter,
+}
+
+impl InfluxFile {
+    pub fn new(filename: Option<String>, output: BufWriter<Box<dyn Write>>) -> InfluxFile {
+        InfluxFile {
+            filename,
+            output,
+            formatter: LineFormatter::new(),
+        }
+    }
+
+    fn write_line(&mut self, line: &str) -> Result<()> {
+        writeln!(self.output, "{line}")
+            .and_then(|_| self.output.flush())
+            .with_context(|| {
+                if let Some(filename) = self.filename.as_deref() {
+                    format!("Writing line protocol to file '{filename}' failed")
+                } else {
+                    "Writing line protocol to stdout failed".into()
+                }
+            })
+    }
+}
+
+impl Output for InfluxFile {
+    fn write_header(&mut self, header: &Header) -> Result<()> {
+        self.formatter.set_tags(header);
+        Ok(())
+    }
+
+    fn write_reading(&mut self, sample: &Sample) -> Result<()> {
+        let line = self.formatter.sample(sample);
+        self.write_line(&line)
+    }
+
+    fn write_comment(&mut self, comment: &str) -> Result<()> {
+        let line = self.formatter.comment(comment);
+        self.write_line(&line)
+    }
+}
+
+/// Pushes line protocol in batches to an InfluxDB write endpoint.
+///
+/// Lines are handed over to a background thread, so a slow or unreachable
+/// server never delays the measurements. Failed pushes are retried with
+/// exponential backoff while lines keep being buffered, up to `MAX_BUFFERED`
+/// lines. When the buffer overflows, the oldest lines are discarded.
+pub struct InfluxHttp {
+    formatter: LineFormatter,
+    sender: Option<Sender<String>>,
+    pusher: Option<JoinHandle<()>>,
+}
+
+impl InfluxHttp {
+    pub fn new(url: &str, token: Option<&str>) -> InfluxHttp {
+        let (sender, receiver) = mpsc::channel();
+
+        let url = url.to_string();
+        let token = token.map(String::from);
+
+        let pusher = thread::spawn(move || push_lines(&url, token.as_deref(), receiver));
+
+        InfluxHttp {
+            formatter: LineFormatter::new(),
+            sender: Some(sender),
+            pusher: Some(pusher),
+        }
+    }
+
+    fn send_line(&mut self, line: String) -> Result<()> {
+        if let Some(sender) = self.sender.as_ref() {
+            sender
+                .send(line)
+                .context("Pushing line protocol to InfluxDB stopped")?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for InfluxHttp {
+    fn drop(&mut self) {
+        self.sender.take();
+
+        let Some(pusher) = self.pusher.take() else {
+            return;
+        };
+
+        // The pusher gives up after the final push timeout, the margin covers
+        // a request started just before.
+        let deadline = Instant::now() + FINAL_PUSH_TIMEOUT + Duration::from_secs(1);
+
+        while !pusher.is_finished() {
+            if Instant::now() >= deadline {
+                eprintln!("InfluxDB not responding, remaining lines discarded");
+                return;
+            }
+            thread::sleep(Duration::from_millis(50));
+        }
+
+        let _ = pusher.join();
+    }
+}
+
+impl Output for InfluxHttp {
+    fn write_header(&mut self, header: &Header) -> Result<()> {
+        self.formatter.set_tags(header);
+        Ok(())
+    }
+
+    fn write_reading(&mut self, sample: &Sample) -> Result<()> {
+        let line = self.formatter.sample(sample);
+        self.send_line(line)
+    }
+
+    fn write_comment(&mut self, comment: &str) -> Result<()> {
+        let line = self.formatter.comment(comment);
+        self.send_line(line)
+    }
+}
+
+fn push_lines(url: &str, token: Option<&str>, receiver: Receiver<String>) {
+    let mut buffer = VecDeque::new();
+    let mut discarded = 0usize;
+    let mut backoff = MIN_BACKOFF;
+    let mut next_push = Instant::now() + BATCH_INTERVAL;
+    let mut closed_at = None;
+
+    loop {
+        let timeout = next_push.saturating_duration_since(Instant::now());
+
+        if closed_at.is_some() {
+            thread::sleep(timeout.min(MIN_BACKOFF));
+        } else {
+            match receiver.recv_timeout(timeout) {
+                Ok(line) => {
+                    buffer.push_back(line);
+                    if buffer.len() > MAX_BUFFERED {
+                        buffer.pop_front();
+                        discarded += 1;
+                    }
+
+                    let healthy = backoff == MIN_BACKOFF;
+                    if Instant::now() < next_push && !(healthy && buffer.len() >= BATCH_SIZE) {
+                        continue;
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => {
+                    closed_at = Some(Instant::now());
+                }
+            }
+        }
+
+        if discarded > 0 {
+            eprintln!("InfluxDB buffer overflow, {discarded} lines discarded");
+            discarded = 0;
+        }
+
+        while !buffer.is_empty() {
+            let timeout = match closed_at {
+                Some(closed_at) => FINAL_PUSH_TIMEOUT.saturating_sub(closed_at.elapsed()),
+                None => REQUEST_TIMEOUT,
+            };
+
+            if timeout.is_zero() {
+                break;
+            }
+
+            let count = buffer.len().min(BATCH_SIZE);
+            let body = buffer
+                .range(..count)
+                .fold(String::new(), |body, line| body + line + "\n");
+
+            let mut request = ureq::post(url)
+                .timeout(timeout.min(REQUEST_TIMEOUT))
+                .set("Content-Type", "text/plain; charset=utf-8");
+            if let Some(token) = token {
+                request = request.set("Authorization", &format!("Token {token}"));
+            }
+
+            match request.send_string(&body) {
+                Ok(_) => {
+                    buffer.drain(..count);
+                    backoff = MIN_BACKOFF;
+                }
+                Err(ureq::Error::Status(code, response)) if code < 500 && code != 429 => {
+                    let text = response.into_string().unwrap_or_default();
+                    eprintln!("InfluxDB rejected {count} lines with status {code}: {text}");
+                    buffer.drain(..count);
+                }
+                Err(err) => {
+                    eprintln!("Pushing to InfluxDB failed, retrying in {backoff:?}: {err}");
+                    break;
+                }
+            }
+        }
+
+        if let Some(closed_at) = closed_at {
+            if buffer.is_empty() || closed_at.elapsed() >= FINAL_PUSH_TIMEOUT {
+                if !buffer.is_empty() {
+                    eprintln!("InfluxDB unreachable, {} lines discarded", buffer.len());
+                }
+                return;
+            }
+        }
+
+        if buffer.is_empty() {
+            next_push = Instant::now() + BATCH_INTERVAL;
+        } else {
+            next_push = Instant::now() + backoff;
+            backoff = (backoff * 2).min(MAX_BACKOFF);
+        }
+    }
+}
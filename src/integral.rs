@@ -0,0 +1,46 @@
+use crate::derived::Channel;
+
+/// Running integral of an expression over time by the trapezoidal rule, in
+/// units of the expression times hours, e.g. Ah for a current.
+pub struct Integral {
+    pub channel: Channel,
+    /// Moment and value of the previous valid sample.
+    previous: Option<(f64, f64)>,
+    total: f64,
+}
+
+impl Integral {
+    pub fn new(channel: Channel) -> Integral {
+        Integral {
+            channel,
+            previous: None,
+            total: 0.0,
+        }
+    }
+
+    /// Adds the value of the expression at `moment` seconds, returns the
+    /// integral so far.
+    ///
+    /// Samples missing in between are bridged by the straight line between
+    /// their neighbours, values which are not finite are skipped.
+    pub fn add(&mut self, moment: f64, value: f64) -> f64 {
+        if value.is_finite() {
+            if let Some((previous_moment, previous_value)) = self.previous {
+                self.total += (moment - previous_moment) * (previous_value + value) / 2.0 / 3600.0;
+            }
+            self.previous = Some((moment, value));
+        }
+
+        self.total
+    }
+
+    /// Stops bridging to the next sample, e.g. across a pause, where nothing
+    /// was measured.
+    pub fn interrupt(&mut self) {
+        self.previous = None;
+    }
+
+    pub fn total(&self) -> f64 {
+        self.total
+    }
+}
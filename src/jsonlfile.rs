@@ -0,0 +1,190 @@
+use std::io::{BufWriter, Write};
+
+use anyhow::{Context, Result};
+use chrono::prelude::*;
+use serde_json::{json, Map, Value};
+
+use crate::output::{Footer, Header, Output, Sample};
+
+const PKG_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+const FIELDS: [(&str, &str); 6] = [
+    ("sequence", "Sequential sample number starting at 0"),
+    (
+        "datetime",
+        "Local date and time of measurement in RFC 3339 format with milliseconds",
+    ),
+    ("moment", "Time in seconds since first measurement"),
+    (
+        "delay",
+        "Delay of the measurement in seconds, caused by non-real-time behavior or fast logging rate",
+    ),
+    (
+        "latency",
+        "Measurement duration in seconds including network roundtrip time",
+    ),
+    ("reading", "Measured value returned from instrument"),
+];
+
+pub struct JsonlFile {
+    filename: Option<String>,
+    output: BufWriter<Box<dyn Write>>,
+    columns: Vec<String>,
+}
+
+impl JsonlFile {
+    pub fn new(filename: Option<String>, output: BufWriter<Box<dyn Write>>) -> JsonlFile {
+        JsonlFile {
+            filename,
+            output,
+            columns: Vec::new(),
+        }
+    }
+
+    fn write_object(&mut self, object: &Value) -> Result<()> {
+        serde_json::to_writer(&mut self.output, object)?;
+        writeln!(self.output)?;
+        self.output.flush()?;
+        Ok(())
+    }
+
+    fn context(&self, what: &str) -> String {
+        if let Some(filename) = self.filename.as_deref() {
+            format!("Writing {what} to JSON Lines file '{filename}' failed")
+        } else {
+            format!("Writing {what} to stdout failed")
+        }
+    }
+}
+
+impl Output for JsonlFile {
+    fn write_header(&mut self, header: &Header) -> Result<()> {
+        self.columns = column_names(header);
+        self.write_object(&metadata_object(header))
+            .with_context(|| self.context("metadata"))
+    }
+
+    fn write_reading(&mut self, sample: &Sample) -> Result<()> {
+        self.write_object(&sample_object(sample, &self.columns))
+            .with_context(|| self.context("data"))
+    }
+
+    fn write_comment(&mut self, comment: &str) -> Result<()> {
+        self.write_object(&comment_object(comment))
+            .with_context(|| self.context("comment"))
+    }
+
+    fn write_footer(&mut self, footer: &Footer) -> Result<()> {
+        self.write_object(&summary_object(footer))
+            .with_context(|| self.context("summary"))
+    }
+}
+
+pub fn metadata_object(header: &Header) -> Value {
+    let settings = header
+        .settings
+        .iter()
+        .map(|(label, value)| (label.clone(), Value::from(value.as_str())))
+        .collect::<Map<_, _>>();
+
+    let fields = FIELDS
+        .iter()
+        .map(|(name, description)| (name.to_string(), Value::from(*description)))
+        .chain(header.columns.iter().map(|column| {
+            (
+                column.name.clone(),
+                Value::from(column.description.as_str()),
+            )
+        }))
+        .collect::<Map<_, _>>();
+
+    let ident = header.identification;
+
+    json!({
+        "type": "metadata",
+        "created": Local::now().to_rfc3339_opts(SecondsFormat::Secs, false),
+        "version": PKG_VERSION,
+        "message": header.message.map(str::trim),
+        "settings": settings,
+        "instrument": {
+            "manufacturer": ident.manufacturer,
+            "model": ident.model,
+            "serial": ident.serial,
+            "firmware": ident.firmware,
+        },
+        "function": header.function,
+        "fields": fields,
+    })
+}
+
+pub fn column_names(header: &Header) -> Vec<String> {
+    header
+        .columns
+        .iter()
+        .map(|column| column.name.clone())
+        .collect()
+}
+
+/// Creates the sample object, with members for the additional columns
+/// named by `columns`.
+pub fn sample_object(sample: &Sample, columns: &[String]) -> Value {
+    let mut object = json!({
+        "type": "sample",
+        "sequence": sample.sequence,
+        "datetime": sample.datetime.to_rfc3339_opts(SecondsFormat::Millis, false),
+        "moment": sample.moment,
+        "delay": sample.delay,
+        "latency": sample.latency,
+        "reading": sample.reading,
+    });
+
+    if let Value::Object(members) = &mut object {
+        for (name, value) in columns.iter().zip(&sample.extra) {
+            members.insert(name.clone(), Value::from(*value));
+        }
+    }
+
+    object
+}
+
+pub fn comment_object(comment: &str) -> Value {
+    json!({
+        "type": "comment",
+        "text": comment,
+    })
+}
+
+pub fn summary_object(footer: &Footer) -> Value {
+    let stats = footer.statistics;
+
+    let statistics = (stats.count() > 0).then(|| {
+        json!({
+            "mean": stats.mean(),
+            "std_dev": stats.std_dev(),
+            "min": stats.min(),
+            "max": stats.max(),
+            "peak_to_peak": stats.peak_to_peak(),
+        })
+    });
+
+    let integrals = footer
+        .integrals
+        .iter()
+        .map(|(name, total)| (name.clone(), Value::from(*total)))
+        .collect::<Map<_, _>>();
+
+    json!({
+        "type": "summary",
+        "finished": footer.finished.to_rfc3339_opts(SecondsFormat::Millis, false),
+        "stopped": footer.stop_reason(),
+        "total": footer.total(),
+        "dropped": footer.dropped,
+        "failed": footer.failed,
+        "late": footer.late,
+        "unchanged": footer.unchanged,
+        "captures": footer.captures,
+        "untriggered": footer.untriggered,
+        "integrals": integrals,
+        "statistics": statistics,
+    })
+}
@@ -1,14 +1,32 @@
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use clap::Parser;
 
+mod adaptive;
+mod alarm;
+mod annotate;
 mod app;
 mod cli;
+mod compress;
+mod control;
 mod csvfile;
+mod derived;
+mod filter;
+mod influx;
 mod instrument;
+mod integral;
+mod jsonlfile;
+mod metrics;
+mod mqtt;
+mod output;
+mod parquetfile;
+mod scaling;
 mod scpi;
+mod socket;
+mod sqlitefile;
 mod status;
-
-use csvfile::CsvFile;
+mod trigger;
+mod tui;
+mod web;
 
 fn main() -> Result<()> {
     let cli = cli::Cli::parse();
@@ -27,25 +45,102 @@ fn main() -> Result<()> {
     let sample_period = cli.sample_period();
     let num_samples = cli.num_samples();
 
-    let (mut output, bar) = if let Some(filename) = cli.output() {
-        (
-            CsvFile::create_new(filename)?,
-            status::MyProgressBar::new(num_samples),
-        )
+    let destinations = cli.destinations()?;
+
+    let control = control::Control::new();
+
+    let socket = cli
+        .control()
+        .map(|path| socket::ControlSocket::bind(path, control.handle()))
+        .transpose()?;
+
+    let to_stdout = destinations
+        .iter()
+        .any(|destination| destination.filename.is_none());
+
+    if cli.tui() && to_stdout {
+        bail!("Terminal UI can not be used while writing to stdout");
+    }
+
+    let bar = if cli.tui() {
+        status::MyProgressBar::hidden(cli.stats_window())
+    } else if !to_stdout {
+        status::MyProgressBar::new(num_samples, cli.stats_window())
     } else {
-        (CsvFile::stdout(), status::MyProgressBar::none())
+        status::MyProgressBar::none(cli.stats_window())
     };
 
-    output.write_header(&cli.describe(), &identification, message)?;
+    let mut output = output::create_all(&destinations, &cli.output_options(), bar.console())?;
+
+    if cli.tui() {
+        let tui = tui::Tui::start(control.handle())?;
+        output = output::tee(output, "terminal UI", Box::new(tui), bar.console());
+    }
+
+    if let Some(address) = cli.web() {
+        let csv_file = destinations
+            .iter()
+            .find(|destination| {
+                destination.format == output::Format::Csv
+                    && destination.compression == compress::Compression::None
+            })
+            .and_then(|destination| destination.filename.as_deref());
 
-    app::run(
-        &mut dmm,
-        output,
+        let dashboard = web::Dashboard::start(address, csv_file)?;
+        output = output::tee(output, "web dashboard", Box::new(dashboard), bar.console());
+    }
+
+    let settings = app::Settings {
         sample_period,
         num_samples,
-        bar,
-        cli.drop_slow_samples(),
-    )?;
+        drop_slow_samples: cli.drop_slow_samples(),
+        late: cli.late(),
+        deadband: cli.deadband(),
+        heartbeat: cli.heartbeat(),
+        adaptive: cli.adaptive(),
+        trigger: cli.trigger(),
+        alarms: cli.alarms(),
+        filters: cli.filters(),
+        show_filtered: cli.show_filtered(),
+        scaling: cli.scaling()?,
+        derived: cli.derived()?,
+        integrals: cli.integrals()?,
+        marker_column: cli.marker_column(),
+        start_at: cli.start_at(),
+        align: cli.align(),
+        duration: cli.duration(),
+        stop_at: cli.stop_at(),
+    };
+
+    let function = cli.function();
+
+    output.write_header(&output::Header {
+        settings: &cli.describe(),
+        identification: &identification,
+        message,
+        function: function.as_deref(),
+        columns: &app::columns(&settings),
+    })?;
+
+    let metrics = if let Some(address) = cli.metrics() {
+        metrics::Exporter::start(address, &identification, function.as_deref())?
+    } else {
+        metrics::Exporter::none()
+    };
+
+    let annotations = if cli.tui() {
+        None
+    } else {
+        Some(annotate::Annotations::start(
+            control.handle(),
+            bar.progress_bar(),
+        ))
+    };
+
+    app::run(&mut dmm, output.as_mut(), &settings, bar, metrics, &control)?;
+
+    drop(annotations);
+    drop(socket);
 
     instrument::unconfigure(&mut dmm, cli.unconfiguration_commands())?;
 
@@ -0,0 +1,163 @@
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use anyhow::{anyhow, Context, Result};
+use chrono::prelude::*;
+use tiny_http::{Header, Response, Server};
+
+use crate::scpi::Identification;
+
+#[derive(Default)]
+struct State {
+    labels: String,
+    reading: Option<f64>,
+    latency: Option<f64>,
+    timestamp: Option<f64>,
+    samples: u64,
+    dropped: u64,
+    errors: u64,
+}
+
+/// Prometheus exporter serving the state of the running measurement on
+/// `/metrics`.
+pub struct Exporter(Option<Arc<Mutex<State>>>);
+
+impl Exporter {
+    pub fn start(
+        address: &str,
+        ident: &Identification,
+        function: Option<&str>,
+    ) -> Result<Exporter> {
+        let server = Server::http(address)
+            .map_err(|err| anyhow!("{err}"))
+            .with_context(|| format!("Starting metrics server on `{address}` failed"))?;
+
+        let mut labels = vec![
+            ("manufacturer", ident.manufacturer.as_str()),
+            ("model", ident.model.as_str()),
+            ("serial", ident.serial.as_str()),
+        ];
+
+        if let Some(function) = function {
+            labels.push(("function", function));
+        }
+
+        let labels = labels
+            .into_iter()
+            .map(|(name, value)| format!("{name}=\"{}\"", escape_label(value)))
+            .collect::<Vec<_>>()
+            .join(",");
+
+        let state = Arc::new(Mutex::new(State {
+            labels,
+            ..Default::default()
+        }));
+
+        let shared = Arc::clone(&state);
+        thread::spawn(move || serve(server, &shared));
+
+        Ok(Exporter(Some(state)))
+    }
+
+    pub fn none() -> Exporter {
+        Exporter(None)
+    }
+
+    pub fn update(&self, datetime: DateTime<Local>, latency: f64, reading: f64) {
+        self.with_state(|state| {
+            state.reading = Some(reading);
+            state.latency = Some(latency);
+            state.timestamp = Some(datetime.timestamp_millis() as f64 / 1000.0);
+            state.samples += 1;
+        });
+    }
+
+    pub fn dropped(&self) {
+        self.with_state(|state| state.dropped += 1);
+    }
+
+    pub fn error(&self) {
+        self.with_state(|state| state.errors += 1);
+    }
+
+    fn with_state(&self, f: impl FnOnce(&mut State)) {
+        if let Self(Some(state)) = self {
+            f(&mut state.lock().unwrap());
+        }
+    }
+}
+
+fn serve(server: Server, state: &Mutex<State>) {
+    let content_type =
+        Header::from_bytes("Content-Type", "text/plain; version=0.0.4; charset=utf-8").unwrap();
+
+    for request in server.incoming_requests() {
+        let response = if request.url() == "/metrics" {
+            let text = render(&state.lock().unwrap());
+            Response::from_string(text).with_header(content_type.clone())
+        } else {
+            Response::from_string("Not Found\n").with_status_code(404)
+        };
+
+        let _ = request.respond(response);
+    }
+}
+
+fn render(state: &State) -> String {
+    let mut text = String::new();
+
+    let mut metric = |name: &str, kind: &str, help: &str, value: Option<f64>| {
+        if let Some(value) = value {
+            let _ = writeln!(text, "# HELP {name} {help}");
+            let _ = writeln!(text, "# TYPE {name} {kind}");
+            let _ = writeln!(text, "{name}{{{}}} {value}", state.labels);
+        }
+    };
+
+    metric(
+        "dmm_reading",
+        "gauge",
+        "Latest measured value returned from instrument",
+        state.reading,
+    );
+    metric(
+        "dmm_latency_seconds",
+        "gauge",
+        "Latest measurement duration including network roundtrip time",
+        state.latency,
+    );
+    metric(
+        "dmm_last_sample_timestamp_seconds",
+        "gauge",
+        "Time of the latest measurement since epoch",
+        state.timestamp,
+    );
+    metric(
+        "dmm_samples_total",
+        "counter",
+        "Number of samples taken",
+        Some(state.samples as f64),
+    );
+    metric(
+        "dmm_dropped_samples_total",
+        "counter",
+        "Number of samples dropped because of delay or latency",
+        Some(state.dropped as f64),
+    );
+    metric(
+        "dmm_errors_total",
+        "counter",
+        "Number of failed measurements",
+        Some(state.errors as f64),
+    );
+
+    text
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
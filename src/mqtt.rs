@@ -0,0 +1,279 @@
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Context, Result};
+use clap::ValueEnum;
+use rumqttc::{Client, Connection, Event, MqttOptions, Outgoing, Packet, QoS, RecvTimeoutError};
+use serde_json::Value;
+
+use crate::jsonlfile;
+use crate::output::{Footer, Header, Output, Sample};
+
+pub const DEFAULT_PORT: u16 = 1883;
+
+const KEEP_ALIVE: Duration = Duration::from_secs(30);
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+const DISCONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[allow(clippy::enum_variant_names)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Qos {
+    #[value(name = "0")]
+    AtMostOnce,
+    #[value(name = "1")]
+    AtLeastOnce,
+    #[value(name = "2")]
+    ExactlyOnce,
+}
+
+impl From<Qos> for QoS {
+    fn from(qos: Qos) -> QoS {
+        match qos {
+            Qos::AtMostOnce => QoS::AtMostOnce,
+            Qos::AtLeastOnce => QoS::AtLeastOnce,
+            Qos::ExactlyOnce => QoS::ExactlyOnce,
+        }
+    }
+}
+
+/// Publishes samples as JSON objects to an MQTT broker.
+///
+/// The header is published as retained message to the metadata topic, so
+/// late subscribers still learn about the running measurement. Comments are
+/// published to the metadata topic as well, but not retained.
+///
+/// While the broker is unreachable, up to `buffer` messages are kept and
+/// published after reconnecting. Further messages are discarded. When closed,
+/// the connection is kept until all messages are acknowledged by the broker or
+/// `DISCONNECT_TIMEOUT` expired.
+pub struct MqttOutput {
+    client: Client,
+    connection: Option<JoinHandle<()>>,
+    progress: Arc<Progress>,
+    qos: QoS,
+    topic_template: String,
+    meta_topic_template: String,
+    topic: String,
+    meta_topic: String,
+    columns: Vec<String>,
+    discarded: usize,
+}
+
+impl MqttOutput {
+    pub fn connect(
+        url: &str,
+        topic_template: &str,
+        meta_topic_template: &str,
+        qos: Qos,
+        buffer: usize,
+    ) -> Result<MqttOutput> {
+        let options = parse_url(url)?;
+
+        let (client, connection) = Client::new(options, buffer.max(1));
+
+        let progress = Arc::new(Progress::default());
+
+        let url = url.to_string();
+        let qos = qos.into();
+        let connection = {
+            let client = client.clone();
+            let progress = Arc::clone(&progress);
+            thread::spawn(move || drive(connection, client, &url, qos, &progress))
+        };
+
+        Ok(MqttOutput {
+            client,
+            connection: Some(connection),
+            progress,
+            qos,
+            topic_template: topic_template.into(),
+            meta_topic_template: meta_topic_template.into(),
+            topic: String::new(),
+            meta_topic: String::new(),
+            columns: Vec::new(),
+            discarded: 0,
+        })
+    }
+
+    fn publish(&mut self, topic: &str, retain: bool, payload: &Value) {
+        let payload = payload.to_string();
+
+        if self
+            .client
+            .try_publish(topic, self.qos, retain, payload)
+            .is_ok()
+        {
+            self.progress.queued.fetch_add(1, Ordering::Relaxed);
+        } else {
+            if self.discarded == 0 {
+                eprintln!("MQTT buffer full, discarding messages");
+            }
+            self.discarded += 1;
+        }
+    }
+}
+
+impl Drop for MqttOutput {
+    fn drop(&mut self) {
+        if self.discarded > 0 {
+            eprintln!(
+                "MQTT buffer overflow, {} messages discarded",
+                self.discarded
+            );
+        }
+
+        self.progress.closing.store(true, Ordering::Relaxed);
+
+        if let Some(connection) = self.connection.take() {
+            let _ = connection.join();
+        }
+    }
+}
+
+impl Output for MqttOutput {
+    fn write_header(&mut self, header: &Header) -> Result<()> {
+        self.topic = expand_topic(&self.topic_template, header);
+        self.meta_topic = expand_topic(&self.meta_topic_template, header);
+        self.columns = jsonlfile::column_names(header);
+
+        let topic = self.meta_topic.clone();
+        self.publish(&topic, true, &jsonlfile::metadata_object(header));
+
+        Ok(())
+    }
+
+    fn write_reading(&mut self, sample: &Sample) -> Result<()> {
+        let topic = self.topic.clone();
+        let object = jsonlfile::sample_object(sample, &self.columns);
+        self.publish(&topic, false, &object);
+        Ok(())
+    }
+
+    fn write_comment(&mut self, comment: &str) -> Result<()> {
+        let topic = self.meta_topic.clone();
+        self.publish(&topic, false, &jsonlfile::comment_object(comment));
+        Ok(())
+    }
+
+    fn write_footer(&mut self, footer: &Footer) -> Result<()> {
+        let topic = self.meta_topic.clone();
+        self.publish(&topic, false, &jsonlfile::summary_object(footer));
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct Progress {
+    closing: AtomicBool,
+    queued: AtomicUsize,
+}
+
+fn drive(mut connection: Connection, client: Client, url: &str, qos: QoS, progress: &Progress) {
+    let mut online = true;
+    let mut completed = 0;
+    let mut deadline = None;
+
+    loop {
+        if progress.closing.load(Ordering::Relaxed) {
+            let deadline = *deadline.get_or_insert_with(|| Instant::now() + DISCONNECT_TIMEOUT);
+            let queued = progress.queued.load(Ordering::Relaxed);
+            if Instant::now() >= deadline {
+                eprintln!(
+                    "MQTT broker `{url}` unreachable, {} messages not delivered",
+                    queued.saturating_sub(completed)
+                );
+                return;
+            }
+            if completed >= queued {
+                let _ = client.try_disconnect();
+            }
+        }
+
+        match connection.recv_timeout(POLL_INTERVAL) {
+            Ok(Ok(Event::Outgoing(Outgoing::Disconnect))) => return,
+            Ok(Ok(Event::Outgoing(Outgoing::Publish(_)))) if qos == QoS::AtMostOnce => {
+                completed += 1;
+            }
+            Ok(Ok(Event::Incoming(Packet::PubAck(_)))) if qos == QoS::AtLeastOnce => {
+                completed += 1;
+            }
+            Ok(Ok(Event::Incoming(Packet::PubComp(_)))) if qos == QoS::ExactlyOnce => {
+                completed += 1;
+            }
+            Ok(Ok(Event::Incoming(Packet::ConnAck(_)))) => {
+                if !online {
+                    eprintln!("MQTT broker `{url}` reconnected");
+                }
+                online = true;
+            }
+            Ok(Ok(_)) => {}
+            Ok(Err(err)) => {
+                if online {
+                    eprintln!("MQTT connection to `{url}` failed: {err}");
+                }
+                online = false;
+                thread::sleep(RECONNECT_DELAY);
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => return,
+        }
+    }
+}
+
+fn parse_url(url: &str) -> Result<MqttOptions> {
+    let Some(address) = url.strip_prefix("mqtt://") else {
+        bail!("MQTT URL `{url}` must start with mqtt://");
+    };
+
+    let address = address.trim_end_matches('/');
+
+    let (credentials, address) = match address.rsplit_once('@') {
+        Some((credentials, address)) => (Some(credentials), address),
+        None => (None, address),
+    };
+
+    let (host, port) = match address.rsplit_once(':') {
+        Some((host, port)) => (
+            host,
+            port.parse()
+                .with_context(|| format!("Invalid port in MQTT URL `{url}`"))?,
+        ),
+        None => (address, DEFAULT_PORT),
+    };
+
+    if host.is_empty() {
+        bail!("Missing host in MQTT URL `{url}`");
+    }
+
+    let mut options = MqttOptions::new(format!("dmm-logger-{}", std::process::id()), host, port);
+    options.set_keep_alive(KEEP_ALIVE);
+
+    if let Some(credentials) = credentials {
+        let (username, password) = credentials.split_once(':').unwrap_or((credentials, ""));
+        options.set_credentials(username, password);
+    }
+
+    Ok(options)
+}
+
+fn expand_topic(template: &str, header: &Header) -> String {
+    let ident = header.identification;
+
+    [
+        ("{manufacturer}", ident.manufacturer.as_str()),
+        ("{model}", ident.model.as_str()),
+        ("{serial}", ident.serial.as_str()),
+        ("{function}", header.function.unwrap_or("none")),
+    ]
+    .iter()
+    .fold(template.to_string(), |topic, (placeholder, value)| {
+        topic.replace(placeholder, &topic_level(value))
+    })
+}
+
+fn topic_level(value: &str) -> String {
+    value.trim().replace(['/', '+', '#'], "_").replace(' ', "-")
+}
@@ -0,0 +1,667 @@
+use std::fs::File;
+use std::io::{stdout, BufWriter, Write};
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use chrono::prelude::*;
+use clap::ValueEnum;
+
+use crate::compress::{self, Compression};
+use crate::csvfile::CsvFile;
+use crate::influx::{InfluxFile, InfluxHttp};
+use crate::jsonlfile::JsonlFile;
+use crate::mqtt::{MqttOutput, Qos};
+use crate::parquetfile::ParquetFile;
+use crate::scpi::Identification;
+use crate::sqlitefile::SqliteFile;
+use crate::status::{self, Console, Statistics};
+
+pub struct Header<'a> {
+    pub settings: &'a [(String, String)],
+    pub identification: &'a Identification,
+    pub message: Option<&'a str>,
+    pub function: Option<&'a str>,
+    pub columns: &'a [Column],
+}
+
+/// Additional numeric column following the reading.
+#[derive(Clone)]
+pub struct Column {
+    pub name: String,
+    pub description: String,
+}
+
+impl Column {
+    pub fn new(name: impl Into<String>, description: impl Into<String>) -> Column {
+        Column {
+            name: name.into(),
+            description: description.into(),
+        }
+    }
+}
+
+pub struct Sample {
+    pub sequence: u32,
+    pub datetime: DateTime<Local>,
+    pub moment: f64,
+    pub delay: f64,
+    pub latency: f64,
+    pub reading: f64,
+    /// Values of the additional columns, `None` for empty cells.
+    pub extra: Vec<Option<f64>>,
+}
+
+pub enum Stop {
+    Completed,
+    /// Duration or stop time reached, with the reason.
+    Scheduled(&'static str),
+    Interrupted(&'static str),
+    Requested(String),
+    Failed(String),
+}
+
+pub struct Footer<'a> {
+    pub finished: DateTime<Local>,
+    pub samples: u32,
+    pub dropped: u32,
+    pub failed: u32,
+    /// Samples measured after their planned time.
+    pub late: u32,
+    /// Samples not written because of the deadband.
+    pub unchanged: u32,
+    /// Number of captures in trigger mode.
+    pub captures: Option<u32>,
+    /// Samples not written, because no capture was triggered.
+    pub untriggered: u32,
+    /// Names and totals of the integrals.
+    pub integrals: &'a [(String, f64)],
+    pub stop: &'a Stop,
+    pub statistics: &'a Statistics,
+}
+
+impl Footer<'_> {
+    pub fn stop_reason(&self) -> String {
+        match self.stop {
+            Stop::Completed => "Number of samples reached".into(),
+            Stop::Scheduled(reason) => reason.to_string(),
+            Stop::Interrupted(signal) => format!("Interrupted by {signal}"),
+            Stop::Requested(source) => format!("Stopped from {source}"),
+            Stop::Failed(err) => format!("Error: {err}"),
+        }
+    }
+
+    pub fn total(&self) -> u32 {
+        self.samples + self.dropped + self.failed + self.unchanged + self.untriggered
+    }
+
+    pub fn describe(&self) -> Vec<(String, String)> {
+        let mut infos = vec![
+            (
+                "Finished".into(),
+                self.finished.format("%Y-%m-%d %H:%M:%S (%Z)").to_string(),
+            ),
+            ("Stopped".into(), self.stop_reason()),
+            ("Total samples".into(), self.total().to_string()),
+            ("Dropped samples".into(), self.dropped.to_string()),
+            ("Failed samples".into(), self.failed.to_string()),
+            ("Late samples".into(), self.late.to_string()),
+            ("Unchanged samples".into(), self.unchanged.to_string()),
+        ];
+
+        if let Some(captures) = self.captures {
+            infos.extend([
+                ("Captures".into(), captures.to_string()),
+                ("Untriggered samples".into(), self.untriggered.to_string()),
+            ]);
+        }
+
+        for (name, total) in self.integrals {
+            infos.push((format!("Integral {name}"), status::precise(*total)));
+        }
+
+        let stats = self.statistics;
+
+        if stats.count() > 0 {
+            infos.extend([
+                ("Mean".into(), status::precise(stats.mean())),
+                ("Std. deviation".into(), status::precise(stats.std_dev())),
+                ("Minimum".into(), status::precise(stats.min())),
+                ("Maximum".into(), status::precise(stats.max())),
+                ("Peak-to-peak".into(), status::precise(stats.peak_to_peak())),
+            ]);
+        }
+
+        infos
+    }
+}
+
+pub trait Output {
+    fn write_header(&mut self, header: &Header) -> Result<()>;
+    fn write_reading(&mut self, sample: &Sample) -> Result<()>;
+    fn write_comment(&mut self, comment: &str) -> Result<()>;
+
+    /// Writes the summary at the end of the run, as comments by default.
+    fn write_footer(&mut self, footer: &Footer) -> Result<()> {
+        for (label, value) in footer.describe() {
+            self.write_comment(&format!("{label}: {value}"))?;
+        }
+        Ok(())
+    }
+
+    /// Closes the current file and continues in a new one, if supported.
+    fn rotate(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called regularly while waiting for the next sample, so writes held
+    /// back for batching do not stay pending while nothing else is written.
+    fn idle(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    Csv,
+    Jsonl,
+    Sqlite,
+    Parquet,
+    Influx,
+    Mqtt,
+}
+
+#[derive(Clone)]
+pub struct Destination {
+    pub filename: Option<String>,
+    pub format: Format,
+    pub compression: Compression,
+}
+
+impl Destination {
+    /// Parses `[FORMAT:]FILE`, where the file `-` is stdout. Format and
+    /// compression default to the ones given by the file extension.
+    pub fn parse(spec: &str) -> Result<Destination> {
+        let (format, filename) = match spec.split_once(':') {
+            Some((prefix, filename)) if !filename.starts_with("//") => {
+                match Format::from_str(prefix, true) {
+                    Ok(format) => (Some(format), filename),
+                    Err(_) => (None, spec),
+                }
+            }
+            _ => (None, spec),
+        };
+
+        if filename.is_empty() {
+            bail!("Missing filename in output `{spec}`");
+        }
+
+        let filename = (filename != "-").then(|| filename.to_string());
+
+        let format = format.unwrap_or_else(|| {
+            filename
+                .as_deref()
+                .map(Format::from_filename)
+                .unwrap_or(Format::Csv)
+        });
+
+        let compression = filename
+            .as_deref()
+            .map(Compression::from_filename)
+            .unwrap_or(Compression::None);
+
+        Ok(Destination {
+            filename,
+            format,
+            compression,
+        })
+    }
+
+    fn describe(&self) -> &str {
+        self.filename.as_deref().unwrap_or("stdout")
+    }
+}
+
+#[derive(Clone)]
+pub struct Options {
+    pub row_group_size: usize,
+    pub influx_token: Option<String>,
+    pub mqtt_topic: String,
+    pub mqtt_meta_topic: String,
+    pub mqtt_qos: Qos,
+    pub mqtt_buffer: usize,
+}
+
+impl Format {
+    pub fn from_filename(filename: &str) -> Format {
+        if is_url(filename) {
+            return Format::Influx;
+        }
+
+        if is_mqtt_url(filename) {
+            return Format::Mqtt;
+        }
+
+        let filename = filename.trim_end_matches(".gz").trim_end_matches(".zst");
+
+        if filename.ends_with(".jsonl") {
+            Format::Jsonl
+        } else if [".sqlite", ".sqlite3", ".db"]
+            .iter()
+            .any(|extension| filename.ends_with(extension))
+        {
+            Format::Sqlite
+        } else if filename.ends_with(".parquet") {
+            Format::Parquet
+        } else if filename.ends_with(".lp") {
+            Format::Influx
+        } else {
+            Format::Csv
+        }
+    }
+}
+
+/// Writes to a primary output and any number of secondary outputs.
+///
+/// Errors of the primary output are returned, while a failing secondary
+/// output is reported on stderr and as comment in the primary output, and
+/// is not written to anymore. Failed rotations keep all outputs, which
+/// continue in their current files.
+pub struct Tee {
+    primary: Box<dyn Output>,
+    secondaries: Vec<(String, Option<Box<dyn Output>>)>,
+    console: Console,
+}
+
+impl Tee {
+    fn each_secondary(&mut self, mut f: impl FnMut(&mut dyn Output) -> Result<()>) -> Result<()> {
+        let mut failures = Vec::new();
+
+        for (name, secondary) in self.secondaries.iter_mut() {
+            if let Some(output) = secondary {
+                if let Err(err) = f(output.as_mut()) {
+                    let failure = format!("Output to {name} disabled: {err:#}");
+                    self.console.print(&failure);
+                    failures.push(failure);
+                    secondary.take();
+                }
+            }
+        }
+
+        for failure in failures {
+            self.primary.write_comment(&failure)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Output for Tee {
+    fn write_header(&mut self, header: &Header) -> Result<()> {
+        self.primary.write_header(header)?;
+        self.each_secondary(|output| output.write_header(header))
+    }
+
+    fn write_reading(&mut self, sample: &Sample) -> Result<()> {
+        self.primary.write_reading(sample)?;
+        self.each_secondary(|output| output.write_reading(sample))
+    }
+
+    fn write_comment(&mut self, comment: &str) -> Result<()> {
+        self.primary.write_comment(comment)?;
+        self.each_secondary(|output| output.write_comment(comment))
+    }
+
+    fn write_footer(&mut self, footer: &Footer) -> Result<()> {
+        self.primary.write_footer(footer)?;
+        self.each_secondary(|output| output.write_footer(footer))
+    }
+
+    fn rotate(&mut self) -> Result<()> {
+        let mut failures = Vec::new();
+
+        if let Err(err) = self.primary.rotate() {
+            failures.push(format!("{err:#}"));
+        }
+
+        for (_, output) in self.secondaries.iter_mut() {
+            if let Some(Err(err)) = output.as_mut().map(|output| output.rotate()) {
+                failures.push(format!("{err:#}"));
+            }
+        }
+
+        if !failures.is_empty() {
+            bail!("{}", failures.join("; "));
+        }
+
+        Ok(())
+    }
+
+    fn idle(&mut self) -> Result<()> {
+        self.primary.idle()?;
+        self.each_secondary(|output| output.idle())
+    }
+}
+
+/// Adds a secondary output, named `name` in case of failure.
+pub fn tee(
+    primary: Box<dyn Output>,
+    name: &str,
+    secondary: Box<dyn Output>,
+    console: Console,
+) -> Box<dyn Output> {
+    Box::new(Tee {
+        primary,
+        secondaries: vec![(name.into(), Some(secondary))],
+        console,
+    })
+}
+
+/// Creates all outputs, the first destination is the primary output.
+/// Failures of the other outputs are printed to the console.
+pub fn create_all(
+    destinations: &[Destination],
+    options: &Options,
+    console: Console,
+) -> Result<Box<dyn Output>> {
+    let mut outputs = destinations
+        .iter()
+        .map(|destination| {
+            let output = create(destination, options)?;
+            let output: Box<dyn Output> = match destination.filename.as_deref() {
+                Some(filename) if !is_url(filename) && !is_mqtt_url(filename) => {
+                    Box::new(Rotating::new(destination, options, output))
+                }
+                _ => output,
+            };
+
+            Ok((destination.describe().to_string(), output))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    if outputs.len() == 1 {
+        return Ok(outputs.remove(0).1);
+    }
+
+    let primary = outputs.remove(0).1;
+    let secondaries = outputs
+        .into_iter()
+        .map(|(name, output)| (name, Some(output)))
+        .collect();
+
+    Ok(Box::new(Tee {
+        primary,
+        secondaries,
+        console,
+    }))
+}
+
+/// Copy of the header, which is written again into rotated files.
+struct SavedHeader {
+    settings: Vec<(String, String)>,
+    identification: Identification,
+    message: Option<String>,
+    function: Option<String>,
+    columns: Vec<Column>,
+}
+
+impl SavedHeader {
+    fn new(header: &Header) -> SavedHeader {
+        SavedHeader {
+            settings: header.settings.to_vec(),
+            identification: header.identification.clone(),
+            message: header.message.map(String::from),
+            function: header.function.map(String::from),
+            columns: header.columns.to_vec(),
+        }
+    }
+
+    fn header(&self) -> Header<'_> {
+        Header {
+            settings: &self.settings,
+            identification: &self.identification,
+            message: self.message.as_deref(),
+            function: self.function.as_deref(),
+            columns: &self.columns,
+        }
+    }
+}
+
+/// Output into a file, which can be rotated while logging.
+///
+/// Rotating renames the file by inserting the current date and time before
+/// the extension, closes it, and creates a new file with the original name
+/// and the same header. Both files note the rotation as comment. A failed
+/// rename keeps logging into the original file.
+struct Rotating {
+    destination: Destination,
+    options: Options,
+    output: Option<Box<dyn Output>>,
+    header: Option<SavedHeader>,
+}
+
+impl Rotating {
+    fn new(destination: &Destination, options: &Options, output: Box<dyn Output>) -> Rotating {
+        Rotating {
+            destination: destination.clone(),
+            options: options.clone(),
+            output: Some(output),
+            header: None,
+        }
+    }
+
+    fn output(&mut self) -> Result<&mut dyn Output> {
+        match self.output.as_mut() {
+            Some(output) => Ok(output.as_mut()),
+            None => bail!(
+                "Output file '{}' closed by failed rotation",
+                self.destination.describe()
+            ),
+        }
+    }
+
+    /// Opens the file with the original name and writes the header again.
+    fn reopen(&mut self) -> Result<&mut dyn Output> {
+        let mut output = create(&self.destination, &self.options)?;
+
+        if let Some(header) = self.header.as_ref() {
+            output.write_header(&header.header())?;
+        }
+
+        Ok(self.output.insert(output).as_mut())
+    }
+}
+
+impl Output for Rotating {
+    fn write_header(&mut self, header: &Header) -> Result<()> {
+        self.header = Some(SavedHeader::new(header));
+        self.output()?.write_header(header)
+    }
+
+    fn write_reading(&mut self, sample: &Sample) -> Result<()> {
+        self.output()?.write_reading(sample)
+    }
+
+    fn write_comment(&mut self, comment: &str) -> Result<()> {
+        self.output()?.write_comment(comment)
+    }
+
+    fn write_footer(&mut self, footer: &Footer) -> Result<()> {
+        self.output()?.write_footer(footer)
+    }
+
+    fn rotate(&mut self) -> Result<()> {
+        let filename = self.destination.describe().to_string();
+        let rotated = rotated_filename(&filename, Local::now());
+
+        if Path::new(&rotated).exists() {
+            bail!("Rotating output file '{filename}' failed: '{rotated}' already exists");
+        }
+
+        let Some(mut output) = self.output.take() else {
+            bail!("Output file '{filename}' closed by failed rotation");
+        };
+
+        let rotation = format!("Rotated, continued in new file '{filename}'");
+
+        let closing = if self.destination.format == Format::Sqlite {
+            // SQLite loses the writes in its write-ahead log, when the database
+            // is renamed while open. It is closed first and opened again, with
+            // a new run, if renaming fails.
+            let closing = output.write_comment(&rotation);
+            drop(output);
+
+            if let Err(err) = std::fs::rename(&filename, &rotated) {
+                self.reopen()?;
+                return Err(err).with_context(|| {
+                    format!("Renaming output file '{filename}' to '{rotated}' failed")
+                });
+            }
+
+            closing
+        } else {
+            // Renaming the open file keeps the output intact, if it fails.
+            if let Err(err) = std::fs::rename(&filename, &rotated) {
+                self.output = Some(output);
+                return Err(err).with_context(|| {
+                    format!("Renaming output file '{filename}' to '{rotated}' failed")
+                });
+            }
+
+            let closing = output.write_comment(&rotation);
+            drop(output);
+            closing
+        };
+
+        self.reopen()?
+            .write_comment(&format!("Continued from '{rotated}'"))?;
+
+        closing
+    }
+
+    fn idle(&mut self) -> Result<()> {
+        match self.output.as_mut() {
+            Some(output) => output.idle(),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Inserts the date and time before the first extension of the filename.
+fn rotated_filename(filename: &str, datetime: DateTime<Local>) -> String {
+    let timestamp = datetime.format("%Y%m%d-%H%M%S");
+    let name_start = filename.rfind('/').map_or(0, |index| index + 1);
+
+    match filename[name_start..].find('.').filter(|&index| index > 0) {
+        Some(index) => {
+            let (stem, extension) = filename.split_at(name_start + index);
+            format!("{stem}-{timestamp}{extension}")
+        }
+        None => format!("{filename}-{timestamp}"),
+    }
+}
+
+pub fn create(destination: &Destination, options: &Options) -> Result<Box<dyn Output>> {
+    let filename = destination.filename.as_deref();
+    let format = destination.format;
+    let compression = destination.compression;
+
+    if let Some(url) = filename.filter(|filename| is_url(filename) || is_mqtt_url(filename)) {
+        if format != Format::from_filename(url) {
+            bail!("Output format does not match '{url}'");
+        }
+
+        if compression != Compression::None {
+            bail!("Output to '{url}' can not be compressed");
+        }
+    }
+
+    Ok(match format {
+        Format::Csv => Box::new(CsvFile::new(
+            filename.map(String::from),
+            create_writer(filename, compression)?,
+        )),
+        Format::Jsonl => Box::new(JsonlFile::new(
+            filename.map(String::from),
+            create_writer(filename, compression)?,
+        )),
+        Format::Sqlite => Box::new(SqliteFile::open(require_uncompressed_file(
+            "SQLite",
+            filename,
+            compression,
+        )?)?),
+        Format::Parquet => Box::new(ParquetFile::create_new(
+            require_uncompressed_file("Parquet", filename, compression)?,
+            options.row_group_size,
+        )?),
+        Format::Influx => match filename.filter(|filename| is_url(filename)) {
+            Some(url) => Box::new(InfluxHttp::new(url, options.influx_token.as_deref())),
+            None => Box::new(InfluxFile::new(
+                filename.map(String::from),
+                create_writer(filename, compression)?,
+            )),
+        },
+        Format::Mqtt => {
+            let Some(url) = filename.filter(|filename| is_mqtt_url(filename)) else {
+                bail!("MQTT output requires an URL starting with mqtt://");
+            };
+
+            Box::new(MqttOutput::connect(
+                url,
+                &options.mqtt_topic,
+                &options.mqtt_meta_topic,
+                options.mqtt_qos,
+                options.mqtt_buffer,
+            )?)
+        }
+    })
+}
+
+fn is_url(filename: &str) -> bool {
+    filename.starts_with("http://") || filename.starts_with("https://")
+}
+
+fn is_mqtt_url(filename: &str) -> bool {
+    filename.starts_with("mqtt://")
+}
+
+fn require_uncompressed_file<'a>(
+    format: &str,
+    filename: Option<&'a str>,
+    compression: Compression,
+) -> Result<&'a str> {
+    let Some(filename) = filename else {
+        bail!("{format} output requires a filename");
+    };
+
+    if compression != Compression::None {
+        bail!("{format} output can not be compressed");
+    }
+
+    Ok(filename)
+}
+
+fn create_writer(
+    filename: Option<&str>,
+    compression: Compression,
+) -> Result<BufWriter<Box<dyn Write>>> {
+    let output: Box<dyn Write> = if let Some(filename) = filename {
+        Box::new(
+            File::options()
+                .create_new(true)
+                .write(true)
+                .open(filename)
+                .with_context(|| format!("Creating output file '{filename}' failed"))?,
+        )
+    } else {
+        Box::new(stdout())
+    };
+
+    let output = compress::writer(output, compression).with_context(|| {
+        format!(
+            "Setting up compression for '{}' failed",
+            filename.unwrap_or("stdout")
+        )
+    })?;
+
+    Ok(BufWriter::new(output))
+}
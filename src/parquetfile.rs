@@ -0,0 +1,263 @@
+use std::fs::File;
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use chrono::prelude::*;
+use parquet::basic::{Compression, ZstdLevel};
+use parquet::data_type::{DoubleType, Int32Type, Int64Type};
+use parquet::file::metadata::KeyValue;
+use parquet::file::properties::WriterProperties;
+use parquet::file::writer::SerializedFileWriter;
+use parquet::schema::parser::parse_message_type;
+
+use crate::output::{Header, Output, Sample};
+
+const PKG_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Fixed part of the schema, followed by the additional columns.
+const SCHEMA: &str = "
+    REQUIRED INT32 sequence (INTEGER(32, false));
+    REQUIRED INT64 datetime (TIMESTAMP(MICROS, true));
+    REQUIRED DOUBLE moment;
+    REQUIRED DOUBLE delay;
+    REQUIRED DOUBLE latency;
+    REQUIRED DOUBLE reading;
+";
+
+const FIELDS: [(&str, &str); 6] = [
+    ("sequence", "Sequential sample number starting at 0"),
+    ("datetime", "Date and time of measurement"),
+    ("moment", "Time in seconds since first measurement"),
+    (
+        "delay",
+        "Delay of the measurement in seconds, caused by non-real-time behavior or fast logging rate",
+    ),
+    (
+        "latency",
+        "Measurement duration in seconds including network roundtrip time",
+    ),
+    ("reading", "Measured value returned from instrument"),
+];
+
+/// Writes samples column-wise into a Parquet file.
+///
+/// Samples are buffered and written as a row group every `row_group_size`
+/// samples. The header is stored as key/value metadata, which is written
+/// together with the comments when the file is closed. The file is written
+/// after the header, which defines the additional columns.
+pub struct ParquetFile {
+    filename: String,
+    file: Option<File>,
+    writer: Option<SerializedFileWriter<File>>,
+    row_group_size: usize,
+    sequence: Vec<i32>,
+    datetime: Vec<i64>,
+    moment: Vec<f64>,
+    delay: Vec<f64>,
+    latency: Vec<f64>,
+    reading: Vec<f64>,
+    extra: Vec<Vec<Option<f64>>>,
+    comments: Vec<String>,
+}
+
+impl ParquetFile {
+    pub fn create_new(filename: &str, row_group_size: usize) -> Result<ParquetFile> {
+        let file = File::options()
+            .create_new(true)
+            .write(true)
+            .open(filename)
+            .with_context(|| format!("Creating Parquet file '{filename}' failed"))?;
+
+        Ok(ParquetFile {
+            filename: filename.into(),
+            file: Some(file),
+            writer: None,
+            row_group_size,
+            sequence: Vec::with_capacity(row_group_size),
+            datetime: Vec::with_capacity(row_group_size),
+            moment: Vec::with_capacity(row_group_size),
+            delay: Vec::with_capacity(row_group_size),
+            latency: Vec::with_capacity(row_group_size),
+            reading: Vec::with_capacity(row_group_size),
+            extra: Vec::new(),
+            comments: Vec::new(),
+        })
+    }
+
+    fn create_writer(&mut self, header: &Header) -> Result<()> {
+        let Some(file) = self.file.take() else {
+            return Ok(());
+        };
+
+        let extra = header
+            .columns
+            .iter()
+            .map(|column| format!("OPTIONAL DOUBLE {};\n", column.name))
+            .collect::<String>();
+
+        let schema = Arc::new(parse_message_type(&format!(
+            "message dmm_logger {{ {SCHEMA} {extra} }}"
+        ))?);
+
+        let properties = WriterProperties::builder()
+            .set_compression(Compression::ZSTD(ZstdLevel::default()))
+            .set_created_by(format!("DMM logger ({PKG_VERSION})"))
+            .build();
+
+        self.writer = Some(SerializedFileWriter::new(
+            file,
+            schema,
+            Arc::new(properties),
+        )?);
+
+        self.extra = header
+            .columns
+            .iter()
+            .map(|_| Vec::with_capacity(self.row_group_size))
+            .collect();
+
+        Ok(())
+    }
+
+    fn append_metadata(&mut self, key: &str, value: impl Into<String>) {
+        if let Some(writer) = self.writer.as_mut() {
+            writer.append_key_value_metadata(KeyValue::new(key.into(), value.into()));
+        }
+    }
+
+    fn write_row_group(&mut self) -> Result<()> {
+        let Some(writer) = self.writer.as_mut() else {
+            return Ok(());
+        };
+
+        if self.sequence.is_empty() {
+            return Ok(());
+        }
+
+        let mut row_group = writer.next_row_group()?;
+
+        macro_rules! write_column {
+            ($type:ty, $values:expr) => {
+                let mut column = row_group.next_column()?.unwrap();
+                column.typed::<$type>().write_batch(&$values, None, None)?;
+                column.close()?;
+                $values.clear();
+            };
+        }
+
+        write_column!(Int32Type, self.sequence);
+        write_column!(Int64Type, self.datetime);
+        write_column!(DoubleType, self.moment);
+        write_column!(DoubleType, self.delay);
+        write_column!(DoubleType, self.latency);
+        write_column!(DoubleType, self.reading);
+
+        for values in &mut self.extra {
+            let levels = values
+                .iter()
+                .map(|value| value.is_some().into())
+                .collect::<Vec<i16>>();
+            let present = values.iter().flatten().copied().collect::<Vec<_>>();
+
+            let mut column = row_group.next_column()?.unwrap();
+            column
+                .typed::<DoubleType>()
+                .write_batch(&present, Some(&levels), None)?;
+            column.close()?;
+            values.clear();
+        }
+
+        row_group.close()?;
+
+        Ok(())
+    }
+
+    fn close(&mut self) -> Result<()> {
+        self.write_row_group()?;
+
+        if !self.comments.is_empty() {
+            let comments = self.comments.join("\n");
+            self.append_metadata("comments", comments);
+        }
+
+        if let Some(writer) = self.writer.take() {
+            writer.close()?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for ParquetFile {
+    fn drop(&mut self) {
+        if let Err(err) = self.close() {
+            eprintln!("Closing Parquet file '{}' failed: {err}", self.filename);
+        }
+    }
+}
+
+impl Output for ParquetFile {
+    fn write_header(&mut self, header: &Header) -> Result<()> {
+        self.create_writer(header)
+            .with_context(|| format!("Creating Parquet writer for '{}' failed", self.filename))?;
+
+        let ident = header.identification;
+
+        self.append_metadata(
+            "created",
+            Local::now().to_rfc3339_opts(SecondsFormat::Secs, false),
+        );
+
+        if let Some(message) = header.message {
+            self.append_metadata("message", message.trim());
+        }
+
+        for (label, value) in header.settings {
+            self.append_metadata(&format!("settings.{label}"), value);
+        }
+
+        self.append_metadata("instrument.manufacturer", &ident.manufacturer);
+        self.append_metadata("instrument.model", &ident.model);
+        self.append_metadata("instrument.serial", &ident.serial);
+        self.append_metadata("instrument.firmware", &ident.firmware);
+
+        for (name, description) in FIELDS {
+            self.append_metadata(&format!("fields.{name}"), description);
+        }
+
+        for column in header.columns {
+            self.append_metadata(&format!("fields.{}", column.name), &column.description);
+        }
+
+        Ok(())
+    }
+
+    fn write_reading(&mut self, sample: &Sample) -> Result<()> {
+        self.sequence.push(sample.sequence as i32);
+        self.datetime.push(sample.datetime.timestamp_micros());
+        self.moment.push(sample.moment);
+        self.delay.push(sample.delay);
+        self.latency.push(sample.latency);
+        self.reading.push(sample.reading);
+
+        for (values, value) in self.extra.iter_mut().zip(&sample.extra) {
+            values.push(*value);
+        }
+
+        if self.sequence.len() >= self.row_group_size {
+            self.write_row_group().with_context(|| {
+                format!(
+                    "Writing row group to Parquet file '{}' failed",
+                    self.filename
+                )
+            })?;
+        }
+
+        Ok(())
+    }
+
+    fn write_comment(&mut self, comment: &str) -> Result<()> {
+        self.comments.push(comment.into());
+        Ok(())
+    }
+}
@@ -0,0 +1,65 @@
+use anyhow::{bail, Result};
+
+use crate::status;
+
+/// Linear conversion of the reading into the quantity measured by a sensor,
+/// `value = gain * reading + offset`.
+#[derive(Debug, Clone)]
+pub struct Scaling {
+    pub gain: f64,
+    pub offset: f64,
+    pub unit: Option<String>,
+    /// Raw readings and values the scaling was calibrated with.
+    pub points: Option<[(f64, f64); 2]>,
+}
+
+impl Scaling {
+    /// Scaling through two calibration points of raw reading and value.
+    pub fn calibrated(points: [(f64, f64); 2], unit: Option<String>) -> Result<Scaling> {
+        let [(raw1, value1), (raw2, value2)] = points;
+
+        if raw1 == raw2 {
+            bail!("Calibration points need different readings");
+        }
+
+        let gain = (value2 - value1) / (raw2 - raw1);
+
+        Ok(Scaling {
+            gain,
+            offset: value1 - gain * raw1,
+            unit,
+            points: Some(points),
+        })
+    }
+
+    pub fn apply(&self, reading: f64) -> f64 {
+        status::rounded(self.gain * reading + self.offset)
+    }
+
+    /// Formula of the scaling, e.g. `value = 625 * reading - 2.5 [bar]`.
+    pub fn formula(&self) -> String {
+        let mut formula = format!("value = {} * reading", self.gain);
+
+        if self.offset < 0.0 {
+            formula.push_str(&format!(" - {}", -self.offset));
+        } else if self.offset > 0.0 {
+            formula.push_str(&format!(" + {}", self.offset));
+        }
+
+        if let Some(unit) = &self.unit {
+            formula.push_str(&format!(" [{unit}]"));
+        }
+
+        formula
+    }
+
+    pub fn describe(&self) -> String {
+        match self.points {
+            Some([(raw1, value1), (raw2, value2)]) => format!(
+                "{}, calibrated {raw1} -> {value1} and {raw2} -> {value2}",
+                self.formula()
+            ),
+            None => self.formula(),
+        }
+    }
+}
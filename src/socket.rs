@@ -0,0 +1,134 @@
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::thread;
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+
+use crate::control::{Command, Handle};
+
+const HELP: &str =
+    "OK commands: pause, resume, interval SECONDS, marker [TEXT], status, stats, rotate, stop";
+
+/// Unix domain socket accepting commands for the running measurement.
+///
+/// Every line received is one command, which is answered by one line
+/// starting with `OK` or `ERROR`. The socket is only accessible by the
+/// owner and removed when logging finished.
+pub struct ControlSocket {
+    path: String,
+}
+
+impl ControlSocket {
+    pub fn bind(path: &str, control: Handle) -> Result<ControlSocket> {
+        remove_stale(path)?;
+
+        let listener = UnixListener::bind(path)
+            .with_context(|| format!("Creating control socket '{path}' failed"))?;
+
+        fs::set_permissions(path, fs::Permissions::from_mode(0o600))
+            .with_context(|| format!("Setting permissions of control socket '{path}' failed"))?;
+
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let control = control.clone();
+                thread::spawn(move || serve(stream, &control));
+            }
+        });
+
+        Ok(ControlSocket { path: path.into() })
+    }
+}
+
+impl Drop for ControlSocket {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Removes a socket left over by a crashed logger, but neither other files
+/// nor sockets still in use.
+fn remove_stale(path: &str) -> Result<()> {
+    let Ok(metadata) = fs::symlink_metadata(path) else {
+        return Ok(());
+    };
+
+    if !metadata.file_type().is_socket() {
+        bail!("Control socket '{path}' already exists and is not a socket");
+    }
+
+    if UnixStream::connect(path).is_ok() {
+        bail!("Control socket '{path}' is in use by another logger");
+    }
+
+    fs::remove_file(path).with_context(|| format!("Removing stale control socket '{path}' failed"))
+}
+
+fn serve(stream: UnixStream, control: &Handle) {
+    let Ok(mut writer) = stream.try_clone() else {
+        return;
+    };
+
+    for line in BufReader::new(stream).lines() {
+        let Ok(line) = line else {
+            return;
+        };
+
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let reply = match parse(line) {
+            Ok(None) => HELP.into(),
+            Ok(Some(command)) => control
+                .request(command)
+                .unwrap_or_else(|err| format!("ERROR {err}")),
+            Err(err) => format!("ERROR {err}"),
+        };
+
+        if writeln!(writer, "{reply}").is_err() {
+            return;
+        }
+    }
+}
+
+/// Parses a command line, `None` is the request for help.
+fn parse(line: &str) -> Result<Option<Command>> {
+    let (name, argument) = match line.split_once(char::is_whitespace) {
+        Some((name, argument)) => (name, Some(argument.trim())),
+        None => (line, None),
+    };
+
+    let command = match (name.to_lowercase().as_str(), argument) {
+        ("help", _) => return Ok(None),
+        ("pause", None) => Command::Pause,
+        ("resume", None) => Command::Resume,
+        ("marker", text) => Command::Marker(text.map(String::from)),
+        ("interval", Some(seconds)) => Command::Interval(parse_interval(seconds)?),
+        ("interval", None) => bail!("Missing interval in seconds"),
+        ("status", None) => Command::Status,
+        ("stats", None) => Command::Statistics,
+        ("rotate", None) => Command::Rotate,
+        ("stop", None) => Command::Stop("control socket".into()),
+        ("pause" | "resume" | "status" | "stats" | "rotate" | "stop", Some(_)) => {
+            bail!("Command `{name}` takes no argument")
+        }
+        _ => bail!("Unknown command `{name}`, try `help`"),
+    };
+
+    Ok(Some(command))
+}
+
+fn parse_interval(seconds: &str) -> Result<Duration> {
+    let seconds = seconds
+        .parse::<f64>()
+        .with_context(|| format!("Invalid interval `{seconds}`"))?;
+
+    match Duration::try_from_secs_f64(seconds) {
+        Ok(interval) if !interval.is_zero() => Ok(interval),
+        _ => bail!("Interval must be greater than 0 seconds"),
+    }
+}
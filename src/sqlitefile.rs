@@ -0,0 +1,273 @@
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Context, Result};
+use chrono::prelude::*;
+use rusqlite::types::Value as SqlValue;
+use rusqlite::{params, params_from_iter, Connection};
+use serde_json::{Map, Value};
+
+use crate::output::{Header, Output, Sample};
+
+const PKG_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+const BATCH_SIZE: usize = 1000;
+const BATCH_INTERVAL: Duration = Duration::from_secs(1);
+
+const SCHEMA: &str = "
+    PRAGMA journal_mode = WAL;
+
+    CREATE TABLE IF NOT EXISTS runs (
+        id INTEGER PRIMARY KEY,
+        started TEXT NOT NULL,
+        version TEXT NOT NULL,
+        message TEXT,
+        settings TEXT NOT NULL,
+        manufacturer TEXT NOT NULL,
+        model TEXT NOT NULL,
+        serial TEXT NOT NULL,
+        firmware TEXT NOT NULL
+    );
+
+    CREATE TABLE IF NOT EXISTS samples (
+        run_id INTEGER NOT NULL REFERENCES runs (id),
+        sequence INTEGER NOT NULL,
+        datetime TEXT NOT NULL,
+        moment REAL NOT NULL,
+        delay REAL NOT NULL,
+        latency REAL NOT NULL,
+        reading REAL NOT NULL,
+        PRIMARY KEY (run_id, sequence)
+    ) WITHOUT ROWID;
+
+    CREATE TABLE IF NOT EXISTS comments (
+        run_id INTEGER NOT NULL REFERENCES runs (id),
+        datetime TEXT NOT NULL,
+        text TEXT NOT NULL
+    );
+";
+
+/// Appends runs to an SQLite database.
+///
+/// Inserts following each other within `BATCH_INTERVAL` are batched into
+/// transactions of up to `BATCH_SIZE` rows, committed at least every
+/// `BATCH_INTERVAL`, also while waiting for the next sample. Slower inserts
+/// are committed immediately. The database runs in WAL mode, so other
+/// processes can query it while logging.
+pub struct SqliteFile {
+    filename: String,
+    conn: Connection,
+    run_id: Option<i64>,
+    insert_sample: String,
+    pending: usize,
+    began: Instant,
+    inserted: Option<Instant>,
+}
+
+impl SqliteFile {
+    pub fn open(filename: &str) -> Result<SqliteFile> {
+        let conn = Connection::open(filename)
+            .with_context(|| format!("Opening SQLite database '{filename}' failed"))?;
+
+        conn.execute_batch(SCHEMA)
+            .with_context(|| format!("Creating tables in SQLite database '{filename}' failed"))?;
+
+        Ok(SqliteFile {
+            filename: filename.into(),
+            conn,
+            run_id: None,
+            insert_sample: String::new(),
+            pending: 0,
+            began: Instant::now(),
+            inserted: None,
+        })
+    }
+
+    fn run_id(&self) -> Result<i64> {
+        match self.run_id {
+            Some(run_id) => Ok(run_id),
+            None => bail!("No run created in SQLite database '{}'", self.filename),
+        }
+    }
+
+    /// Adds the additional columns missing in the samples table, which stay
+    /// empty for older runs.
+    fn add_columns(&mut self, header: &Header) -> Result<()> {
+        let existing = self
+            .conn
+            .prepare("SELECT name FROM pragma_table_info('samples')")?
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        let mut names = Vec::new();
+
+        for column in header.columns {
+            let name = format!("\"{}\"", column.name.replace('"', "\"\""));
+
+            if !existing.contains(&column.name) {
+                self.conn
+                    .execute_batch(&format!("ALTER TABLE samples ADD COLUMN {name} REAL"))?;
+            }
+
+            names.push(name);
+        }
+
+        let placeholders = (1..=7 + names.len())
+            .map(|index| format!("?{index}"))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        let names = [
+            "run_id", "sequence", "datetime", "moment", "delay", "latency", "reading",
+        ]
+        .iter()
+        .map(|name| name.to_string())
+        .chain(names)
+        .collect::<Vec<_>>()
+        .join(", ");
+
+        self.insert_sample = format!("INSERT INTO samples ({names}) VALUES ({placeholders})");
+
+        Ok(())
+    }
+
+    fn insert<P: rusqlite::Params>(&mut self, sql: &str, params: P) -> Result<()> {
+        let batching = self
+            .inserted
+            .is_some_and(|inserted| inserted.elapsed() < BATCH_INTERVAL);
+
+        if batching && self.conn.is_autocommit() {
+            self.conn.execute_batch("BEGIN")?;
+            self.began = Instant::now();
+        }
+
+        self.conn.prepare_cached(sql)?.execute(params)?;
+
+        self.inserted = Some(Instant::now());
+        self.pending += 1;
+
+        if !batching || self.pending >= BATCH_SIZE || self.began.elapsed() >= BATCH_INTERVAL {
+            self.commit()?;
+        }
+
+        Ok(())
+    }
+
+    fn commit(&mut self) -> Result<()> {
+        if !self.conn.is_autocommit() {
+            self.conn.execute_batch("COMMIT")?;
+        }
+        self.pending = 0;
+        Ok(())
+    }
+}
+
+impl Drop for SqliteFile {
+    fn drop(&mut self) {
+        if let Err(err) = self.commit() {
+            eprintln!(
+                "Committing to SQLite database '{}' failed: {err}",
+                self.filename
+            );
+        }
+    }
+}
+
+impl Output for SqliteFile {
+    fn write_header(&mut self, header: &Header) -> Result<()> {
+        let settings = header
+            .settings
+            .iter()
+            .map(|(label, value)| (label.clone(), Value::from(value.as_str())))
+            .collect::<Map<_, _>>();
+
+        let ident = header.identification;
+
+        self.conn
+            .execute(
+                "INSERT INTO runs (started, version, message, settings,
+                                   manufacturer, model, serial, firmware)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                params![
+                    Local::now().to_rfc3339_opts(SecondsFormat::Secs, false),
+                    PKG_VERSION,
+                    header.message.map(str::trim),
+                    Value::Object(settings).to_string(),
+                    ident.manufacturer,
+                    ident.model,
+                    ident.serial,
+                    ident.firmware,
+                ],
+            )
+            .with_context(|| {
+                format!("Creating run in SQLite database '{}' failed", self.filename)
+            })?;
+
+        self.run_id = Some(self.conn.last_insert_rowid());
+
+        self.add_columns(header).with_context(|| {
+            format!(
+                "Adding columns to SQLite database '{}' failed",
+                self.filename
+            )
+        })
+    }
+
+    fn write_reading(&mut self, sample: &Sample) -> Result<()> {
+        let run_id = self.run_id()?;
+
+        let values = [
+            SqlValue::Integer(run_id),
+            SqlValue::Integer(sample.sequence.into()),
+            SqlValue::Text(
+                sample
+                    .datetime
+                    .to_rfc3339_opts(SecondsFormat::Millis, false),
+            ),
+            SqlValue::Real(sample.moment),
+            SqlValue::Real(sample.delay),
+            SqlValue::Real(sample.latency),
+            SqlValue::Real(sample.reading),
+        ]
+        .into_iter()
+        .chain(
+            sample
+                .extra
+                .iter()
+                .map(|value| value.map_or(SqlValue::Null, SqlValue::Real)),
+        )
+        .collect::<Vec<_>>();
+
+        let sql = self.insert_sample.clone();
+
+        self.insert(&sql, params_from_iter(values))
+            .with_context(|| format!("Writing data to SQLite database '{}' failed", self.filename))
+    }
+
+    fn write_comment(&mut self, comment: &str) -> Result<()> {
+        let run_id = self.run_id()?;
+
+        self.insert(
+            "INSERT INTO comments (run_id, datetime, text) VALUES (?1, ?2, ?3)",
+            params![
+                run_id,
+                Local::now().to_rfc3339_opts(SecondsFormat::Millis, false),
+                comment,
+            ],
+        )
+        .with_context(|| {
+            format!(
+                "Writing comment to SQLite database '{}' failed",
+                self.filename
+            )
+        })
+    }
+
+    fn idle(&mut self) -> Result<()> {
+        if self.began.elapsed() < BATCH_INTERVAL {
+            return Ok(());
+        }
+
+        self.commit()
+            .with_context(|| format!("Committing to SQLite database '{}' failed", self.filename))
+    }
+}
@@ -1,31 +1,41 @@
+use std::collections::VecDeque;
 use std::time::Duration;
 
 use console::style;
 use indicatif::{ProgressBar, ProgressStyle};
 
-pub struct MyProgressBar(Option<ProgressBar>);
+pub struct MyProgressBar {
+    bar: Option<ProgressBar>,
+    console: Console,
+    statistics: Statistics,
+    window: Option<Statistics>,
+    alarm: bool,
+    /// Filtered value shown instead of the reading, with the filter name.
+    filtered: Option<(f64, &'static str)>,
+}
 
 impl Drop for MyProgressBar {
     fn drop(&mut self) {
-        if let Self(Some(bar)) = self {
+        if let Some(bar) = &self.bar {
             bar.finish_and_clear();
         }
     }
 }
 
 impl MyProgressBar {
-    pub fn new(num_samples: u32) -> MyProgressBar {
+    pub fn new(num_samples: u32, window: Option<usize>) -> MyProgressBar {
         let bar = ProgressBar::new(num_samples.into());
 
         let spinner = style("{spinner}").green().bold();
         let msg = style("{msg}").bold().magenta();
+        let stats = style("{prefix}").dim();
         let eta = style("({eta})").dim();
 
         let template = if num_samples == u32::MAX {
-            format!("{spinner} [{{elapsed_precise}}] #{{pos}}: {msg}")
+            format!("{spinner} [{{elapsed_precise}}] #{{pos}}: {msg} {stats}")
         } else {
             format!(
-            "{spinner} [{{elapsed_precise}}] {{bar:40.cyan/blue}} #{{pos}}/{{len}}: {msg} {eta}"
+            "{spinner} [{{elapsed_precise}}] {{bar:40.cyan/blue}} #{{pos}}/{{len}}: {msg} {stats} {eta}"
         )
         };
 
@@ -37,17 +47,284 @@ impl MyProgressBar {
 
         bar.enable_steady_tick(Duration::from_millis(100));
 
-        MyProgressBar(Some(bar))
+        MyProgressBar {
+            console: Console::Bar(bar.clone()),
+            bar: Some(bar),
+            statistics: Statistics::new(None),
+            window: window.map(|size| Statistics::new(Some(size))),
+            alarm: false,
+            filtered: None,
+        }
+    }
+
+    pub fn none(window: Option<usize>) -> MyProgressBar {
+        MyProgressBar {
+            bar: None,
+            console: Console::Stderr,
+            statistics: Statistics::new(None),
+            window: window.map(|size| Statistics::new(Some(size))),
+            alarm: false,
+            filtered: None,
+        }
     }
 
-    pub fn none() -> MyProgressBar {
-        MyProgressBar(None)
+    /// No progress bar and no messages, while the terminal UI covers the
+    /// screen.
+    pub fn hidden(window: Option<usize>) -> MyProgressBar {
+        let mut bar = MyProgressBar::none(window);
+        bar.console = Console::Hidden;
+        bar
     }
 
-    pub fn update(&self, reading: f64) {
-        if let Self(Some(bar)) = self {
+    pub fn update(&mut self, reading: f64) {
+        self.statistics.add(reading);
+
+        if let Some(window) = self.window.as_mut() {
+            window.add(reading);
+        }
+
+        if let Some(bar) = &self.bar {
+            let stats = self.window.as_ref().unwrap_or(&self.statistics);
+
+            let shown = match self.filtered {
+                Some((value, name)) => format!("{} ({name})", precise(value)),
+                None => format!("{reading}"),
+            };
+
             bar.inc(1);
-            bar.set_message(format!("{reading}"));
+            if self.alarm {
+                bar.set_message(format!("{}", style(shown).red().reverse()));
+            } else {
+                bar.set_message(shown);
+            }
+            bar.set_prefix(format!(
+                "μ={} σ={} p-p={}",
+                short(stats.mean()),
+                short(stats.std_dev()),
+                short(stats.peak_to_peak()),
+            ));
         }
     }
+
+    /// Highlights the readings while a limit is violated.
+    pub fn set_alarm(&mut self, alarm: bool) {
+        self.alarm = alarm;
+    }
+
+    /// Shows the filtered value instead of the next reading.
+    pub fn set_filtered(&mut self, value: f64, name: &'static str) {
+        self.filtered = Some((value, name));
+    }
+
+    /// Shows the message until the first reading.
+    pub fn set_waiting(&self, message: &str) {
+        if let Some(bar) = &self.bar {
+            bar.set_message(message.to_string());
+        }
+    }
+
+    pub fn progress_bar(&self) -> Option<ProgressBar> {
+        self.bar.clone()
+    }
+
+    pub fn console(&self) -> Console {
+        self.console.clone()
+    }
+
+    pub fn statistics(&self) -> &Statistics {
+        &self.statistics
+    }
+
+    /// Clears the progress bar and prints the statistics to stderr.
+    pub fn finish(&self) {
+        if let Some(bar) = &self.bar {
+            bar.finish_and_clear();
+        }
+
+        if self.statistics.count() == 0 {
+            return;
+        }
+
+        let mut columns = vec![("Run".to_string(), &self.statistics)];
+
+        if let Some(window) = self.window.as_ref() {
+            columns.push((format!("Last {}", window.count()), window));
+        }
+
+        let row = |label: &str, value: fn(&Statistics) -> String| {
+            let mut line = vec![label.to_string()];
+            line.extend(columns.iter().map(|(_, stats)| value(stats)));
+            line
+        };
+
+        let table = [
+            row("Samples", |stats| stats.count().to_string()),
+            row("Mean", |stats| precise(stats.mean())),
+            row("Std. deviation", |stats| precise(stats.std_dev())),
+            row("Minimum", |stats| precise(stats.min())),
+            row("Maximum", |stats| precise(stats.max())),
+            row("Peak-to-peak", |stats| precise(stats.peak_to_peak())),
+        ];
+
+        let mut header = vec!["Statistics".to_string()];
+        header.extend(columns.iter().map(|(title, _)| title.clone()));
+
+        let widths = (0..header.len())
+            .map(|col| {
+                table
+                    .iter()
+                    .chain([&header])
+                    .map(|line| line[col].chars().count())
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect::<Vec<_>>();
+
+        for line in [&header].into_iter().chain(table.iter()) {
+            let text = line
+                .iter()
+                .zip(&widths)
+                .map(|(cell, width)| format!("{cell:width$}"))
+                .collect::<Vec<_>>()
+                .join("  ");
+            eprintln!("{}", text.trim_end());
+        }
+    }
+}
+
+/// Prints messages to stderr while logging, without disturbing the progress
+/// bar or the terminal UI.
+#[derive(Clone)]
+pub enum Console {
+    Stderr,
+    /// Messages are printed above the progress bar.
+    Bar(ProgressBar),
+    /// Messages are not printed, failures are noted as comments anyway.
+    Hidden,
+}
+
+impl Console {
+    pub fn print(&self, message: &str) {
+        match self {
+            Console::Stderr => eprintln!("{message}"),
+            Console::Bar(bar) => bar.suspend(|| eprintln!("{message}")),
+            Console::Hidden => {}
+        }
+    }
+}
+
+/// Running statistics of readings, either over all readings or over a
+/// sliding window of the last readings.
+///
+/// Mean and variance are updated with Welford's algorithm. Readings leaving
+/// the window are removed by reversing the update.
+#[derive(Debug, Clone)]
+pub struct Statistics {
+    size: Option<usize>,
+    values: VecDeque<f64>,
+    count: u64,
+    mean: f64,
+    m2: f64,
+    min: f64,
+    max: f64,
+}
+
+impl Statistics {
+    pub fn new(size: Option<usize>) -> Statistics {
+        Statistics {
+            size,
+            values: VecDeque::new(),
+            count: 0,
+            mean: 0.0,
+            m2: 0.0,
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+        }
+    }
+
+    pub fn add(&mut self, value: f64) {
+        self.count += 1;
+        let delta = value - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (value - self.mean);
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+
+        if let Some(size) = self.size {
+            self.values.push_back(value);
+            if self.values.len() > size {
+                let removed = self.values.pop_front().unwrap();
+                self.remove(removed);
+            }
+        }
+    }
+
+    fn remove(&mut self, value: f64) {
+        let mean = self.mean;
+        self.count -= 1;
+        self.mean = (mean * (self.count + 1) as f64 - value) / self.count as f64;
+        self.m2 = (self.m2 - (value - mean) * (value - self.mean)).max(0.0);
+
+        if value <= self.min || value >= self.max {
+            self.min = self.values.iter().copied().fold(f64::INFINITY, f64::min);
+            self.max = self
+                .values
+                .iter()
+                .copied()
+                .fold(f64::NEG_INFINITY, f64::max);
+        }
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn mean(&self) -> f64 {
+        self.mean
+    }
+
+    /// Sample standard deviation, zero for less than two readings.
+    pub fn std_dev(&self) -> f64 {
+        if self.count > 1 {
+            (self.m2 / (self.count - 1) as f64).sqrt()
+        } else {
+            0.0
+        }
+    }
+
+    pub fn min(&self) -> f64 {
+        self.min
+    }
+
+    pub fn max(&self) -> f64 {
+        self.max
+    }
+
+    pub fn peak_to_peak(&self) -> f64 {
+        self.max - self.min
+    }
+}
+
+/// Formats with 9 significant digits, which covers the resolution of the
+/// best instruments without showing floating point noise.
+pub fn precise(value: f64) -> String {
+    let magnitude = value.abs().log10().floor();
+
+    if value == 0.0 || !value.is_finite() {
+        format!("{value}")
+    } else if (-3.0..9.0).contains(&magnitude) {
+        format!("{value:.*}", (8.0 - magnitude).max(0.0) as usize)
+    } else {
+        format!("{value:.8e}")
+    }
+}
+
+/// Rounds to 12 significant digits, which hides the floating point noise of
+/// calculations with readings.
+pub fn rounded(value: f64) -> f64 {
+    format!("{value:.11e}").parse().unwrap_or(value)
+}
+
+fn short(value: f64) -> String {
+    format!("{value:.4e}")
 }
@@ -0,0 +1,167 @@
+use std::collections::VecDeque;
+
+use crate::output::Sample;
+use crate::status;
+
+/// Conditions starting a capture, any of them triggers.
+#[derive(Debug, Clone, Copy)]
+pub struct Trigger {
+    /// Reading crosses the level in either direction.
+    pub level: Option<f64>,
+    /// Reading leaves the range from low to high.
+    pub window: Option<(f64, f64)>,
+    /// Reading changes by more than this since the previous reading.
+    pub slope: Option<f64>,
+    /// Number of samples written before the triggering sample.
+    pub pre: usize,
+    /// Number of samples written after the triggering sample.
+    pub post: usize,
+}
+
+impl Trigger {
+    pub fn describe(&self) -> String {
+        let mut conditions = Vec::new();
+
+        if let Some(level) = self.level {
+            conditions.push(format!("crossing {level}"));
+        }
+
+        if let Some((low, high)) = self.window {
+            conditions.push(format!("leaving {low} to {high}"));
+        }
+
+        if let Some(slope) = self.slope {
+            conditions.push(format!("change by more than {slope}"));
+        }
+
+        format!(
+            "{}, {} samples before and {} after",
+            conditions.join(" or "),
+            self.pre,
+            self.post
+        )
+    }
+
+    /// Returns the reason, if the reading fulfills a condition.
+    fn check(&self, previous: Option<f64>, reading: f64) -> Option<String> {
+        if let (Some(level), Some(previous)) = (self.level, previous) {
+            if (previous < level && reading >= level) || (previous > level && reading <= level) {
+                return Some(format!("reading {reading} crossed {level}"));
+            }
+        }
+
+        if let Some((low, high)) = self.window {
+            let inside = |value: f64| (low..=high).contains(&value);
+
+            if !inside(reading) && previous.is_none_or(inside) {
+                return Some(format!("reading {reading} left {low} to {high}"));
+            }
+        }
+
+        if let (Some(slope), Some(previous)) = (self.slope, previous) {
+            if (reading - previous).abs() > slope {
+                return Some(format!(
+                    "reading {reading} changed by {}",
+                    status::precise(reading - previous)
+                ));
+            }
+        }
+
+        None
+    }
+}
+
+pub enum Event {
+    Comment(String),
+    Sample(Sample),
+}
+
+/// Keeps the latest samples in a ring buffer and passes them on only around
+/// a trigger.
+///
+/// A trigger during the samples after a trigger extends the capture.
+pub struct Capturer {
+    trigger: Trigger,
+    buffer: VecDeque<Sample>,
+    previous: Option<f64>,
+    /// Number of the running capture and its samples still to write.
+    running: Option<(u32, usize)>,
+    captures: u32,
+    discarded: u32,
+}
+
+impl Capturer {
+    pub fn new(trigger: Trigger) -> Capturer {
+        Capturer {
+            trigger,
+            buffer: VecDeque::with_capacity(trigger.pre),
+            previous: None,
+            running: None,
+            captures: 0,
+            discarded: 0,
+        }
+    }
+
+    /// Adds a sample, returns the comments and samples to write.
+    pub fn add(&mut self, sample: Sample) -> Vec<Event> {
+        let reason = self.trigger.check(self.previous, sample.reading);
+        self.previous = Some(sample.reading);
+
+        let sequence = sample.sequence;
+        let mut events = Vec::new();
+
+        match (self.running, reason) {
+            (Some((capture, _)), Some(_)) => {
+                self.running = Some((capture, self.trigger.post));
+                events.push(Event::Sample(sample));
+            }
+            (Some((capture, remaining)), None) => {
+                self.running = Some((capture, remaining.saturating_sub(1)));
+                events.push(Event::Sample(sample));
+            }
+            (None, Some(reason)) => {
+                self.captures += 1;
+                self.running = Some((self.captures, self.trigger.post));
+
+                events.push(Event::Comment(format!(
+                    "{sequence}: Capture {} triggered, {reason}",
+                    self.captures
+                )));
+                events.extend(self.buffer.drain(..).map(Event::Sample));
+                events.push(Event::Sample(sample));
+            }
+            (None, None) => {
+                if self.trigger.pre == 0 {
+                    self.discarded += 1;
+                    return events;
+                }
+
+                if self.buffer.len() == self.trigger.pre {
+                    self.buffer.pop_front();
+                    self.discarded += 1;
+                }
+                self.buffer.push_back(sample);
+                return events;
+            }
+        }
+
+        if let Some((capture, 0)) = self.running {
+            events.push(Event::Comment(format!(
+                "{}: Capture {capture} finished",
+                sequence + 1
+            )));
+            self.running = None;
+        }
+
+        events
+    }
+
+    pub fn captures(&self) -> u32 {
+        self.captures
+    }
+
+    /// Number of samples not written, including the ones still buffered.
+    pub fn untriggered(&self) -> u32 {
+        self.discarded + self.buffer.len() as u32
+    }
+}
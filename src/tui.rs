@@ -0,0 +1,485 @@
+use std::collections::VecDeque;
+use std::io::{stderr, Stderr};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use ratatui::backend::CrosstermBackend;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::crossterm::execute;
+use ratatui::crossterm::terminal::{
+    disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen,
+};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style, Stylize};
+use ratatui::symbols::Marker;
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Axis, BarChart, Block, Chart, Dataset, GraphType, Paragraph};
+use ratatui::{Frame, Terminal};
+
+use crate::control::{Command, Handle};
+use crate::output::{Footer, Header, Output, Sample};
+use crate::status::{precise, Statistics};
+
+/// Number of samples shown in the charts and the histogram.
+const HISTORY: usize = 600;
+const COMMENTS: usize = 50;
+const BINS: usize = 20;
+const REFRESH_INTERVAL: Duration = Duration::from_millis(200);
+
+struct View {
+    instrument: Vec<(String, String)>,
+    settings: Vec<(String, String)>,
+    message: Option<String>,
+    readings: VecDeque<(f64, f64)>,
+    latencies: VecDeque<(f64, f64)>,
+    delays: VecDeque<(f64, f64)>,
+    last: Option<(u32, f64)>,
+    statistics: Statistics,
+    comments: VecDeque<String>,
+}
+
+/// Full-screen terminal UI showing the running measurement.
+///
+/// The screen is drawn on stderr by a background thread, which also reads
+/// the keyboard and sends the commands to pause and resume logging, insert
+/// a marker or annotation, or quit.
+pub struct Tui {
+    view: Arc<Mutex<View>>,
+    closing: Arc<AtomicBool>,
+    renderer: Option<JoinHandle<Result<()>>>,
+}
+
+impl Tui {
+    pub fn start(control: Handle) -> Result<Tui> {
+        enable_raw_mode().context("Switching terminal to raw mode failed")?;
+
+        let mut terminal = Terminal::new(CrosstermBackend::new(stderr()))
+            .and_then(|mut terminal| {
+                execute!(terminal.backend_mut(), EnterAlternateScreen)?;
+                terminal.clear()?;
+                Ok(terminal)
+            })
+            .inspect_err(|_| {
+                let _ = disable_raw_mode();
+            })
+            .context("Starting terminal UI failed")?;
+
+        let view = Arc::new(Mutex::new(View {
+            instrument: Vec::new(),
+            settings: Vec::new(),
+            message: None,
+            readings: VecDeque::new(),
+            latencies: VecDeque::new(),
+            delays: VecDeque::new(),
+            last: None,
+            statistics: Statistics::new(None),
+            comments: VecDeque::new(),
+        }));
+
+        let closing = Arc::new(AtomicBool::new(false));
+
+        let renderer = {
+            let view = Arc::clone(&view);
+            let closing = Arc::clone(&closing);
+            thread::spawn(move || {
+                let result = render(&mut terminal, &view, &closing, &control);
+                restore(&mut terminal).and(result)
+            })
+        };
+
+        Ok(Tui {
+            view,
+            closing,
+            renderer: Some(renderer),
+        })
+    }
+
+    fn close(&mut self) -> Result<()> {
+        self.closing.store(true, Ordering::Relaxed);
+
+        match self.renderer.take().map(JoinHandle::join) {
+            Some(Ok(result)) => result.context("Terminal UI failed"),
+            Some(Err(_)) => {
+                disable_raw_mode()?;
+                execute!(stderr(), LeaveAlternateScreen)?;
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+
+    fn with_view(&self, f: impl FnOnce(&mut View)) -> Result<()> {
+        if self.renderer.as_ref().is_some_and(JoinHandle::is_finished) {
+            bail!("Terminal UI stopped");
+        }
+
+        f(&mut self.view.lock().unwrap());
+        Ok(())
+    }
+}
+
+impl Drop for Tui {
+    fn drop(&mut self) {
+        let _ = self.close();
+    }
+}
+
+impl Output for Tui {
+    fn write_header(&mut self, header: &Header) -> Result<()> {
+        self.with_view(|view| {
+            let ident = header.identification;
+
+            view.instrument = vec![
+                ("Manufacturer".into(), ident.manufacturer.clone()),
+                ("Model".into(), ident.model.clone()),
+                ("Serial".into(), ident.serial.clone()),
+                ("Firmware".into(), ident.firmware.clone()),
+            ];
+
+            if let Some(function) = header.function {
+                view.instrument.push(("Function".into(), function.into()));
+            }
+
+            view.settings = header.settings.to_vec();
+            view.message = header.message.map(|message| message.trim().into());
+        })
+    }
+
+    fn write_reading(&mut self, sample: &Sample) -> Result<()> {
+        self.with_view(|view| {
+            push(&mut view.readings, (sample.moment, sample.reading), HISTORY);
+            push(
+                &mut view.latencies,
+                (sample.moment, sample.latency),
+                HISTORY,
+            );
+            push(&mut view.delays, (sample.moment, sample.delay), HISTORY);
+            view.last = Some((sample.sequence, sample.reading));
+            view.statistics.add(sample.reading);
+        })
+    }
+
+    fn write_comment(&mut self, comment: &str) -> Result<()> {
+        self.with_view(|view| push(&mut view.comments, comment.into(), COMMENTS))
+    }
+
+    fn write_footer(&mut self, _footer: &Footer) -> Result<()> {
+        self.close()
+    }
+}
+
+fn push<T>(values: &mut VecDeque<T>, value: T, capacity: usize) {
+    if values.len() >= capacity {
+        values.pop_front();
+    }
+    values.push_back(value);
+}
+
+fn render(
+    terminal: &mut Terminal<CrosstermBackend<Stderr>>,
+    view: &Mutex<View>,
+    closing: &AtomicBool,
+    control: &Handle,
+) -> Result<()> {
+    let mut input: Option<String> = None;
+
+    while !closing.load(Ordering::Relaxed) {
+        let paused = control.is_paused();
+
+        terminal.draw(|frame| draw(frame, &view.lock().unwrap(), paused, input.as_deref()))?;
+
+        if !event::poll(REFRESH_INTERVAL)? {
+            continue;
+        }
+
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+
+        if let Some(text) = input.as_mut() {
+            match key.code {
+                KeyCode::Char(c) => text.push(c),
+                KeyCode::Backspace => {
+                    text.pop();
+                }
+                KeyCode::Enter => control.send(Command::Marker(input.take())),
+                KeyCode::Esc => input = None,
+                _ => {}
+            }
+            continue;
+        }
+
+        match key.code {
+            KeyCode::Char('p') | KeyCode::Char(' ') if paused => control.send(Command::Resume),
+            KeyCode::Char('p') | KeyCode::Char(' ') => control.send(Command::Pause),
+            KeyCode::Char('m') => control.send(Command::Marker(None)),
+            KeyCode::Char('a') | KeyCode::Enter => input = Some(String::new()),
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                control.send(Command::Stop("terminal UI".into()))
+            }
+            KeyCode::Char('q') | KeyCode::Esc => control.send(Command::Stop("terminal UI".into())),
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+fn restore(terminal: &mut Terminal<CrosstermBackend<Stderr>>) -> Result<()> {
+    disable_raw_mode()?;
+    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+    terminal.show_cursor()?;
+    Ok(())
+}
+
+fn draw(frame: &mut Frame, view: &View, paused: bool, input: Option<&str>) {
+    let [main, bottom, status] = Layout::vertical([
+        Constraint::Min(10),
+        Constraint::Length(12),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+
+    let [chart, sidebar] =
+        Layout::horizontal([Constraint::Min(40), Constraint::Length(44)]).areas(main);
+
+    let [instrument, settings, statistics] = Layout::vertical([
+        Constraint::Length(view.instrument.len() as u16 + 2),
+        Constraint::Min(3),
+        Constraint::Length(8),
+    ])
+    .areas(sidebar);
+
+    let [histogram, timing, comments] = Layout::horizontal([
+        Constraint::Percentage(35),
+        Constraint::Percentage(35),
+        Constraint::Percentage(30),
+    ])
+    .areas(bottom);
+
+    draw_readings(frame, chart, view);
+    draw_labels(frame, instrument, "Instrument", &view.instrument);
+    draw_settings(frame, settings, view);
+    draw_statistics(frame, statistics, &view.statistics);
+    draw_histogram(frame, histogram, view);
+    draw_timing(frame, timing, view);
+    draw_comments(frame, comments, view);
+    draw_status(frame, status, view, paused, input);
+}
+
+fn draw_readings(frame: &mut Frame, area: Rect, view: &View) {
+    let data = view.readings.iter().copied().collect::<Vec<_>>();
+
+    let (x_min, x_max) = bounds(data.iter().map(|(x, _)| *x));
+    let (y_min, y_max) = bounds(data.iter().map(|(_, y)| *y));
+
+    let dataset = Dataset::default()
+        .marker(Marker::Braille)
+        .graph_type(GraphType::Line)
+        .style(Style::default().fg(Color::Magenta))
+        .data(&data);
+
+    let chart = Chart::new(vec![dataset])
+        .block(Block::bordered().title("Reading"))
+        .x_axis(
+            Axis::default()
+                .title("seconds")
+                .bounds([x_min, x_max])
+                .labels([format!("{x_min:.1}"), format!("{x_max:.1}")]),
+        )
+        .y_axis(Axis::default().bounds([y_min, y_max]).labels([
+            precise(y_min),
+            precise((y_min + y_max) / 2.0),
+            precise(y_max),
+        ]));
+
+    frame.render_widget(chart, area);
+}
+
+fn draw_labels(frame: &mut Frame, area: Rect, title: &str, labels: &[(String, String)]) {
+    let width = labels
+        .iter()
+        .map(|(label, _)| label.len())
+        .max()
+        .unwrap_or(0);
+
+    let lines = labels
+        .iter()
+        .map(|(label, value)| {
+            Line::from(vec![
+                Span::raw(format!("{label:width$} : ")).dim(),
+                Span::raw(value.clone()),
+            ])
+        })
+        .collect::<Vec<_>>();
+
+    frame.render_widget(
+        Paragraph::new(lines).block(Block::bordered().title(title.to_string())),
+        area,
+    );
+}
+
+fn draw_settings(frame: &mut Frame, area: Rect, view: &View) {
+    let mut settings = view.settings.clone();
+
+    if let Some(message) = view.message.as_ref() {
+        settings.insert(
+            0,
+            (
+                "Message".into(),
+                message.lines().next().unwrap_or("").into(),
+            ),
+        );
+    }
+
+    draw_labels(frame, area, "Settings", &settings);
+}
+
+fn draw_statistics(frame: &mut Frame, area: Rect, stats: &Statistics) {
+    let labels = if stats.count() > 0 {
+        vec![
+            ("Samples".into(), stats.count().to_string()),
+            ("Mean".into(), precise(stats.mean())),
+            ("Std. deviation".into(), precise(stats.std_dev())),
+            ("Minimum".into(), precise(stats.min())),
+            ("Maximum".into(), precise(stats.max())),
+            ("Peak-to-peak".into(), precise(stats.peak_to_peak())),
+        ]
+    } else {
+        Vec::new()
+    };
+
+    draw_labels(frame, area, "Statistics", &labels);
+}
+
+fn draw_histogram(frame: &mut Frame, area: Rect, view: &View) {
+    let (min, max) = bounds(view.readings.iter().map(|(_, reading)| *reading));
+
+    let mut counts = [0u64; BINS];
+    for (_, reading) in &view.readings {
+        let bin = ((reading - min) / (max - min) * BINS as f64) as usize;
+        counts[bin.min(BINS - 1)] += 1;
+    }
+
+    let data = counts.iter().map(|count| ("", *count)).collect::<Vec<_>>();
+
+    let inner_width = area.width.saturating_sub(2) as usize;
+    let bar_width = (inner_width / BINS).max(1) as u16;
+
+    let histogram = BarChart::default()
+        .block(Block::bordered().title(format!("Histogram {} .. {}", precise(min), precise(max))))
+        .bar_width(bar_width)
+        .bar_gap(0)
+        .bar_style(Style::default().fg(Color::Cyan))
+        .value_style(Style::default().fg(Color::Black).bg(Color::Cyan))
+        .data(&data);
+
+    frame.render_widget(histogram, area);
+}
+
+fn draw_timing(frame: &mut Frame, area: Rect, view: &View) {
+    let latencies = view.latencies.iter().copied().collect::<Vec<_>>();
+    let delays = view.delays.iter().copied().collect::<Vec<_>>();
+
+    let (x_min, x_max) = bounds(latencies.iter().map(|(x, _)| *x));
+    let (_, y_max) = bounds(
+        latencies
+            .iter()
+            .chain(&delays)
+            .map(|(_, y)| *y)
+            .chain([0.0]),
+    );
+
+    let datasets = vec![
+        Dataset::default()
+            .name("latency")
+            .marker(Marker::Braille)
+            .graph_type(GraphType::Line)
+            .style(Style::default().fg(Color::Green))
+            .data(&latencies),
+        Dataset::default()
+            .name("delay")
+            .marker(Marker::Braille)
+            .graph_type(GraphType::Line)
+            .style(Style::default().fg(Color::Yellow))
+            .data(&delays),
+    ];
+
+    let chart = Chart::new(datasets)
+        .block(Block::bordered().title("Latency / delay"))
+        .x_axis(Axis::default().bounds([x_min, x_max]))
+        .y_axis(
+            Axis::default()
+                .bounds([0.0, y_max])
+                .labels(["0".to_string(), format!("{y_max:.3}s")]),
+        );
+
+    frame.render_widget(chart, area);
+}
+
+fn draw_comments(frame: &mut Frame, area: Rect, view: &View) {
+    let visible = area.height.saturating_sub(2) as usize;
+
+    let lines = view
+        .comments
+        .iter()
+        .skip(view.comments.len().saturating_sub(visible))
+        .map(|comment| Line::raw(comment.clone()))
+        .collect::<Vec<_>>();
+
+    frame.render_widget(
+        Paragraph::new(lines).block(Block::bordered().title("Comments")),
+        area,
+    );
+}
+
+fn draw_status(frame: &mut Frame, area: Rect, view: &View, paused: bool, input: Option<&str>) {
+    let mut spans = Vec::new();
+
+    if let Some((sequence, reading)) = view.last {
+        spans.push(Span::raw(format!(" #{sequence}: ")));
+        spans.push(Span::styled(
+            reading.to_string(),
+            Style::default()
+                .fg(Color::Magenta)
+                .add_modifier(Modifier::BOLD),
+        ));
+    }
+
+    if paused {
+        spans.push(Span::raw("  "));
+        spans.push(Span::raw(" PAUSED ").black().on_yellow());
+    }
+
+    if let Some(input) = input {
+        spans.push(Span::raw(format!("   Annotation: {input}")));
+        spans.push(Span::raw(" ").reversed());
+    } else {
+        spans.push(Span::raw("   p pause/resume   m marker   a annotate   q quit").dim());
+    }
+
+    frame.render_widget(Paragraph::new(Line::from(spans)), area);
+}
+
+/// Returns the range of the values, widened if empty or a single value.
+fn bounds(values: impl Iterator<Item = f64>) -> (f64, f64) {
+    let (min, max) = values.fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), value| {
+        (min.min(value), max.max(value))
+    });
+
+    if !min.is_finite() || !max.is_finite() {
+        (0.0, 1.0)
+    } else if min == max {
+        let margin = if min == 0.0 { 1.0 } else { min.abs() * 1e-6 };
+        (min - margin, max + margin)
+    } else {
+        (min, max)
+    }
+}
@@ -0,0 +1,197 @@
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::Write;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use anyhow::{anyhow, Context, Result};
+use serde_json::{json, Value};
+use tiny_http::{Header as HttpHeader, Request, Response, Server};
+
+use crate::jsonlfile;
+use crate::output::{Footer, Header, Output, Sample};
+
+const INDEX: &str = include_str!("dashboard.html");
+
+/// Number of samples sent to a newly connected browser.
+const HISTORY: usize = 10_000;
+const KEEP_ALIVE: Duration = Duration::from_secs(15);
+
+#[derive(Default)]
+struct State {
+    metadata: Option<Value>,
+    columns: Vec<String>,
+    history: VecDeque<String>,
+    summary: Option<String>,
+    clients: Vec<Sender<String>>,
+}
+
+impl State {
+    fn broadcast(&mut self, event: &str) {
+        self.clients
+            .retain(|client| client.send(event.to_string()).is_ok());
+    }
+}
+
+/// Web dashboard showing the running measurement in a browser.
+///
+/// The page receives the same objects as written to JSON Lines files as
+/// Server-Sent Events on `/events`. A newly connected page first receives
+/// the metadata and the latest samples.
+pub struct Dashboard {
+    state: Arc<Mutex<State>>,
+}
+
+impl Dashboard {
+    pub fn start(address: &str, csv_file: Option<&str>) -> Result<Dashboard> {
+        let server = Server::http(address)
+            .map_err(|err| anyhow!("{err}"))
+            .with_context(|| format!("Starting web dashboard on `{address}` failed"))?;
+
+        let state = Arc::new(Mutex::new(State::default()));
+
+        let shared = Arc::clone(&state);
+        let csv_file = csv_file.map(String::from);
+        thread::spawn(move || serve(server, &shared, csv_file.as_deref()));
+
+        Ok(Dashboard { state })
+    }
+}
+
+impl Drop for Dashboard {
+    fn drop(&mut self) {
+        self.state.lock().unwrap().clients.clear();
+    }
+}
+
+impl Output for Dashboard {
+    fn write_header(&mut self, header: &Header) -> Result<()> {
+        let metadata = jsonlfile::metadata_object(header);
+        let mut state = self.state.lock().unwrap();
+        state.broadcast(&metadata.to_string());
+        state.metadata = Some(metadata);
+        state.columns = jsonlfile::column_names(header);
+        Ok(())
+    }
+
+    fn write_reading(&mut self, sample: &Sample) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        let event = jsonlfile::sample_object(sample, &state.columns).to_string();
+        state.broadcast(&event);
+        if state.history.len() >= HISTORY {
+            state.history.pop_front();
+        }
+        state.history.push_back(event);
+        Ok(())
+    }
+
+    fn write_comment(&mut self, comment: &str) -> Result<()> {
+        let event = jsonlfile::comment_object(comment).to_string();
+        self.state.lock().unwrap().broadcast(&event);
+        Ok(())
+    }
+
+    fn write_footer(&mut self, footer: &Footer) -> Result<()> {
+        let event = jsonlfile::summary_object(footer).to_string();
+        let mut state = self.state.lock().unwrap();
+        state.broadcast(&event);
+        state.summary = Some(event);
+        Ok(())
+    }
+}
+
+fn serve(server: Server, state: &Arc<Mutex<State>>, csv_file: Option<&str>) {
+    for request in server.incoming_requests() {
+        let url = request.url().split('?').next().unwrap_or_default();
+
+        let _ = match url {
+            "/" => request.respond(
+                Response::from_string(INDEX).with_header(content_type("text/html; charset=utf-8")),
+            ),
+            "/events" => {
+                let state = Arc::clone(state);
+                thread::spawn(move || stream_events(request, &state));
+                Ok(())
+            }
+            "/metadata" => {
+                let metadata = state.lock().unwrap().metadata.clone();
+                let metadata = json!({
+                    "metadata": metadata,
+                    "csv": csv_file.is_some(),
+                });
+                request.respond(
+                    Response::from_string(metadata.to_string())
+                        .with_header(content_type("application/json")),
+                )
+            }
+            "/data.csv" => match csv_file.map(File::open) {
+                Some(Ok(file)) => {
+                    request.respond(Response::from_file(file).with_header(content_type("text/csv")))
+                }
+                _ => request.respond(Response::from_string("Not Found\n").with_status_code(404)),
+            },
+            _ => request.respond(Response::from_string("Not Found\n").with_status_code(404)),
+        };
+    }
+}
+
+fn content_type(value: &str) -> HttpHeader {
+    HttpHeader::from_bytes("Content-Type", value).unwrap()
+}
+
+/// Streams the events to one browser until it disconnects or logging ends.
+///
+/// The response is written directly to the connection, because the chunked
+/// encoding of tiny_http buffers the events.
+fn stream_events(request: Request, state: &Mutex<State>) {
+    let (sender, receiver) = mpsc::channel();
+
+    {
+        let mut state = state.lock().unwrap();
+
+        let backlog = state
+            .metadata
+            .iter()
+            .map(Value::to_string)
+            .chain(state.history.iter().cloned())
+            .chain(state.summary.iter().cloned());
+
+        for event in backlog {
+            let _ = sender.send(event);
+        }
+
+        if state.summary.is_none() {
+            state.clients.push(sender);
+        }
+    }
+
+    let mut writer = request.into_writer();
+    let _ = write_events(&mut writer, &receiver);
+}
+
+fn write_events(writer: &mut dyn Write, receiver: &Receiver<String>) -> std::io::Result<()> {
+    write!(
+        writer,
+        "HTTP/1.1 200 OK\r\n\
+         Content-Type: text/event-stream\r\n\
+         Cache-Control: no-cache\r\n\
+         Connection: close\r\n\r\n"
+    )?;
+    writer.flush()?;
+
+    loop {
+        match receiver.recv_timeout(KEEP_ALIVE) {
+            Ok(event) => {
+                write!(writer, "data: {event}\n\n")?;
+                while let Ok(event) = receiver.try_recv() {
+                    write!(writer, "data: {event}\n\n")?;
+                }
+            }
+            Err(RecvTimeoutError::Timeout) => write!(writer, ": keep-alive\n\n")?,
+            Err(RecvTimeoutError::Disconnected) => return Ok(()),
+        }
+        writer.flush()?;
+    }
+}